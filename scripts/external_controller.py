@@ -0,0 +1,44 @@
+#!/usr/bin/env python3
+"""Minimal external controller: lets one vehicle cross at a time, closest first.
+
+    smart-road-batch --scenario scenarios/rush_hour.toml --external tcp:127.0.0.1:7000
+    python3 scripts/external_controller.py tcp:127.0.0.1:7000
+"""
+import json
+import socket
+import sys
+
+
+def connect(address):
+    if address.startswith("unix:"):
+        sock = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+        sock.connect(address[len("unix:"):])
+    else:
+        host, port = address.removeprefix("tcp:").rsplit(":", 1)
+        sock = socket.create_connection((host, int(port)))
+    return sock
+
+
+def decide(observation):
+    vehicles = observation["vehicles"]
+    busy = any(v["granted"] and v["state"] != "cleared" for v in vehicles)
+    if busy:
+        return {}
+    waiting = [v for v in vehicles if not v["granted"] and v["distance_to_stop_line"] < 30]
+    if not waiting:
+        return {}
+    first = min(waiting, key=lambda v: v["distance_to_stop_line"])
+    return {"grant": [first["id"]]}
+
+
+def main():
+    address = sys.argv[1] if len(sys.argv) > 1 else "tcp:127.0.0.1:7000"
+    sock = connect(address)
+    stream = sock.makefile("rw")
+    for line in stream:
+        stream.write(json.dumps(decide(json.loads(line))) + "\n")
+        stream.flush()
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,296 @@
+//! Runs a scenario without a window and writes its statistics.
+//!
+//! `smart-road-batch --scenario <file> [--duration <seconds>] [--seed <n>] [--output <dir>]`
+//!
+//! `--record <file>` also writes every tick of a single run to a recording
+//! that the viewer opens with `--playback`.
+//!
+//! With `--seeds <n>` it runs a sweep instead: every combination of
+//! `--rate-scale`, `--security-distance`, `--speed-limit`, `--controller` and
+//! `--penetration`, each with `n` seeds, and writes the means with their 95%
+//! confidence intervals.
+//!
+//! With `--capacity <step>` it raises the demand by `step` times the scenario's
+//! until the intersection saturates, for each `--controller`, and writes the
+//! delay-vs-volume curve with the maximum sustainable throughput.
+//!
+//! With `--external <address>` a single run is driven by a controller
+//! process connected on `tcp:<host>:<port>` or `unix:<path>`.
+//!
+//! With `--gym <address>` it serves the scenario as a reinforcement-learning
+//! environment to one learner, episodes lasting `--duration` seconds.
+
+use smart_road::capacity::{self, Ramp};
+use smart_road::cli::arg_value;
+use smart_road::export;
+use smart_road::external::Connection;
+use smart_road::gym::{self, EnvConfig, Environment};
+use smart_road::intersection::Intersection;
+use smart_road::recording::Recorder;
+use smart_road::scenario::{ControllerKind, Scenario};
+use smart_road::statistics::Statistics;
+use smart_road::sweep::{self, Sweep};
+use std::fs;
+use std::path::Path;
+use std::process::exit;
+use std::time::Instant;
+
+fn parse<T: std::str::FromStr>(flag: &str) -> Option<T> {
+    let value = arg_value(flag)?;
+    match value.parse() {
+        Ok(value) => Some(value),
+        Err(_) => {
+            eprintln!("invalid value for {}: {}", flag, value);
+            exit(1);
+        }
+    }
+}
+
+/// Values of a swept parameter, `a,b,c` or `start:end:step`.
+fn parse_values(flag: &str) -> Option<Vec<f32>> {
+    let value = arg_value(flag)?;
+    match sweep::parse_values(&value) {
+        Ok(values) => Some(values),
+        Err(err) => {
+            eprintln!("invalid value for {}: {}", flag, err);
+            exit(1);
+        }
+    }
+}
+
+fn parse_controllers() -> Option<Vec<ControllerKind>> {
+    let value = arg_value("--controller")?;
+    let kinds = value
+        .split(',')
+        .map(|name| {
+            let name = name.trim();
+            ControllerKind::ALL
+                .iter()
+                .find(|k| k.name() == name)
+                .copied()
+                .unwrap_or_else(|| {
+                    eprintln!("unknown controller {}", name);
+                    exit(1);
+                })
+        })
+        .collect();
+    Some(kinds)
+}
+
+fn run_sweep(scenario: &Scenario, seeds: u64, duration: f32, output: &str) {
+    let mut sweep = Sweep::new(scenario, seeds, duration);
+    if let Some(values) = parse_values("--rate-scale") {
+        sweep.rate_scales = values;
+    }
+    if let Some(values) = parse_values("--security-distance") {
+        sweep.security_distances = values;
+    }
+    if let Some(values) = parse_values("--speed-limit") {
+        sweep.speed_limits = values;
+    }
+    if let Some(kinds) = parse_controllers() {
+        sweep.controllers = kinds;
+    }
+    if let Some(values) = parse_values("--penetration") {
+        if values.iter().any(|p| !(0.0..=1.0).contains(p)) {
+            eprintln!("--penetration expects shares between 0 and 1");
+            exit(1);
+        }
+        sweep.penetrations = values;
+    }
+    let combinations = sweep.combinations().len();
+    eprintln!(
+        "running {} combinations x {} seeds",
+        combinations, sweep.seeds
+    );
+
+    let started = Instant::now();
+    let rows = sweep.run(scenario).unwrap_or_else(|err| {
+        eprintln!("invalid combination: {}", err);
+        exit(1);
+    });
+    print!("{}", sweep::to_table(&rows));
+    eprintln!(
+        "{} runs in {:.2} s",
+        combinations as u64 * sweep.seeds,
+        started.elapsed().as_secs_f32()
+    );
+    let path = Path::new(output).join("sweep.csv");
+    let saved = fs::create_dir_all(output)
+        .map_err(|err| err.into())
+        .and_then(|_| sweep::save(&rows, &path.to_string_lossy()));
+    if let Err(err) = saved {
+        eprintln!("cannot write {}: {}", path.display(), err);
+        exit(1);
+    }
+    eprintln!("sweep written to {}", path.display());
+}
+
+fn run_capacity(scenario: &Scenario, step: f32, duration: f32, output: &str) {
+    let ramp = Ramp {
+        step,
+        max_scale: parse("--max-rate-scale").unwrap_or(4.0),
+        seeds: parse::<u64>("--seeds").unwrap_or(3).max(1),
+        duration,
+    };
+    let controllers = parse_controllers().unwrap_or(vec![scenario.controller.kind]);
+    let mut points = Vec::new();
+    for controller in controllers {
+        let curve = ramp.run(scenario, controller);
+        println!("{}", controller.name());
+        print!("{}", capacity::to_table(&curve));
+        points.extend(curve);
+    }
+    let path = Path::new(output).join("capacity.csv");
+    let saved = fs::create_dir_all(output)
+        .map_err(|err| err.into())
+        .and_then(|_| capacity::save(&points, &path.to_string_lossy()));
+    if let Err(err) = saved {
+        eprintln!("cannot write {}: {}", path.display(), err);
+        exit(1);
+    }
+    eprintln!("capacity curve written to {}", path.display());
+}
+
+fn capture(recorder: &mut Option<Recorder>, intersection: &Intersection) {
+    let captured = recorder.as_mut().map(|r| r.capture(intersection));
+    if let Some(Err(err)) = captured {
+        eprintln!("recording stopped: {}", err);
+        *recorder = None;
+    }
+}
+
+/// Like `Scenario::run`, capturing every tick when recording.
+fn run_local(scenario: &Scenario, duration: f32, recorder: &mut Option<Recorder>) -> Intersection {
+    let mut intersection = scenario.build();
+    while intersection.time() < duration {
+        scenario.spawn(&mut intersection);
+        intersection.step();
+        capture(recorder, &intersection);
+    }
+    intersection
+}
+
+/// Like `run_local`, asking the external controller before every tick.
+fn run_external(
+    scenario: &Scenario,
+    address: &str,
+    duration: f32,
+    recorder: &mut Option<Recorder>,
+) -> Intersection {
+    eprintln!("waiting for an external controller on {}", address);
+    let mut connection = Connection::listen(address).unwrap_or_else(|err| {
+        eprintln!("cannot listen on {}: {}", address, err);
+        exit(1);
+    });
+    let mut intersection = scenario.build();
+    intersection.controller.kind = ControllerKind::External;
+    while intersection.time() < duration {
+        scenario.spawn(&mut intersection);
+        if let Err(err) = connection.exchange(&mut intersection) {
+            eprintln!(
+                "external controller lost at {:.1} s: {}",
+                intersection.time(),
+                err
+            );
+            exit(1);
+        }
+        intersection.step();
+        capture(recorder, &intersection);
+    }
+    intersection
+}
+
+fn serve_gym(scenario: Scenario, address: &str, duration: f32) {
+    eprintln!("waiting for a learner on {}", address);
+    let mut connection = Connection::listen(address).unwrap_or_else(|err| {
+        eprintln!("cannot listen on {}: {}", address, err);
+        exit(1);
+    });
+    let config = EnvConfig {
+        duration,
+        ..Default::default()
+    };
+    let mut environment = Environment::new(scenario, config);
+    if let Err(err) = gym::serve(&mut environment, &mut connection) {
+        eprintln!("learner connection failed: {}", err);
+        exit(1);
+    }
+}
+
+fn main() {
+    let Some(path) = arg_value("--scenario") else {
+        eprintln!(
+            "usage: smart-road-batch --scenario <file> [--duration <seconds>] [--seed <n>] [--output <dir>]"
+        );
+        exit(1);
+    };
+    let mut scenario = match Scenario::load(&path) {
+        Ok(scenario) => scenario,
+        Err(err) => {
+            eprintln!("cannot load scenario {}: {}", path, err);
+            exit(1);
+        }
+    };
+    if let Some(seed) = parse("--seed") {
+        scenario.seed = seed;
+    }
+    let Some(duration) = parse("--duration").or(scenario.duration) else {
+        eprintln!("{} has no duration, pass --duration <seconds>", path);
+        exit(1);
+    };
+    if let Some(address) = arg_value("--gym") {
+        serve_gym(scenario, &address, duration);
+        return;
+    }
+    let output = arg_value("--output").unwrap_or_else(|| "results".to_string());
+    let external = arg_value("--external");
+    if external.is_none() && scenario.controller.kind == ControllerKind::External {
+        eprintln!("the external controller needs --external <address>");
+        exit(1);
+    }
+    if let Some(step) = parse::<f32>("--capacity") {
+        if step <= 0.0 {
+            eprintln!("--capacity expects a positive step");
+            exit(1);
+        }
+        run_capacity(&scenario, step, duration, &output);
+        return;
+    }
+    if let Some(seeds) = parse::<u64>("--seeds") {
+        run_sweep(&scenario, seeds.max(1), duration, &output);
+        return;
+    }
+
+    let mut recorder = arg_value("--record").map(|path| match Recorder::create(&path) {
+        Ok(recorder) => recorder,
+        Err(err) => {
+            eprintln!("cannot create recording {}: {}", path, err);
+            exit(1);
+        }
+    });
+    let started = Instant::now();
+    let intersection = match &external {
+        Some(address) => run_external(&scenario, address, duration, &mut recorder),
+        None => run_local(&scenario, duration, &mut recorder),
+    };
+    let elapsed = started.elapsed().as_secs_f32();
+    if let Some(Err(err)) = recorder.as_mut().map(|r| r.finish()) {
+        eprintln!("cannot write recording: {}", err);
+    }
+    let mut statistics = Statistics::new();
+    statistics.update(&intersection);
+
+    print!("{}", export::text_report(&statistics));
+    eprintln!(
+        "simulated {:.0} s in {:.2} s ({:.0}x real time)",
+        duration,
+        elapsed,
+        duration / elapsed.max(f32::EPSILON)
+    );
+    if let Err(err) = export::write(&statistics, &output) {
+        eprintln!("cannot export statistics to {}: {}", output, err);
+        exit(1);
+    }
+    eprintln!("statistics written to {}", output);
+}
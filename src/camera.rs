@@ -0,0 +1,88 @@
+use macroquad::prelude::*;
+
+/// Side of the square world drawn by `road.png`.
+const WORLD_SIZE: f32 = 1000.0;
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 8.0;
+const ZOOM_STEP: f32 = 1.1;
+
+/// World-to-screen camera: mouse wheel zooms around the cursor, middle button
+/// (or Ctrl + left button) drags, 0 resets. The whole map fits the window at
+/// zoom 1 whatever its size.
+pub struct ViewCamera {
+    pub target: Vec2,
+    pub zoom: f32,
+    drag_from: Option<Vec2>,
+}
+
+impl Default for ViewCamera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ViewCamera {
+    pub fn new() -> Self {
+        Self {
+            target: vec2(WORLD_SIZE / 2.0, WORLD_SIZE / 2.0),
+            zoom: 1.0,
+            drag_from: None,
+        }
+    }
+
+    /// Screen pixels per world pixel.
+    fn scale(&self) -> f32 {
+        screen_width().min(screen_height()) / WORLD_SIZE * self.zoom
+    }
+
+    pub fn camera(&self) -> Camera2D {
+        let scale = self.scale();
+        Camera2D {
+            target: self.target,
+            zoom: vec2(2.0 * scale / screen_width(), -2.0 * scale / screen_height()),
+            ..Default::default()
+        }
+    }
+
+    pub fn screen_to_world(&self, point: Vec2) -> Vec2 {
+        self.camera().screen_to_world(point)
+    }
+
+    pub fn handle_input(&mut self) {
+        let mouse = Vec2::from(mouse_position());
+
+        let wheel = mouse_wheel().1;
+        if wheel != 0.0 {
+            let before = self.screen_to_world(mouse);
+            let factor = if wheel > 0.0 {
+                ZOOM_STEP
+            } else {
+                1.0 / ZOOM_STEP
+            };
+            self.zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+            let after = self.screen_to_world(mouse);
+            self.target += before - after;
+        }
+
+        let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let dragging = is_mouse_button_down(MouseButton::Middle)
+            || (ctrl && is_mouse_button_down(MouseButton::Left));
+        if dragging {
+            if let Some(from) = self.drag_from {
+                self.target -= (mouse - from) / self.scale();
+            }
+            self.drag_from = Some(mouse);
+        } else {
+            self.drag_from = None;
+        }
+
+        if is_key_pressed(KeyCode::Key0) {
+            *self = ViewCamera::new();
+        }
+    }
+
+    /// Whether the left button is currently used for panning.
+    pub fn is_dragging(&self) -> bool {
+        self.drag_from.is_some()
+    }
+}
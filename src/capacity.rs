@@ -0,0 +1,288 @@
+use crate::constants::SERIES_PERIOD;
+use crate::intersection::Intersection;
+use crate::route::Approach;
+use crate::scenario::*;
+use crate::statistics::*;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+/// Growth of a queue between the second and the last quarter of a run
+/// above which it is considered unbounded.
+const GROWTH_LIMIT: f32 = 1.25;
+/// Vehicles a queue may grow by on top of `GROWTH_LIMIT`, for short queues.
+const GROWTH_MARGIN: f32 = 2.0;
+
+/// Offered and served volumes of one approach, in vehicles per hour.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Volume {
+    pub offered: f32,
+    pub throughput: f32,
+    pub saturated: bool,
+}
+
+/// One demand level of the ramp, averaged over the seeds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapacityPoint {
+    pub controller: ControllerKind,
+    pub rate_scale: f32,
+    pub overall: Volume,
+    pub approaches: BTreeMap<Approach, Volume>,
+    pub mean_delay: f32,
+    pub p95_delay: f32,
+}
+
+/// Raises the demand of a scenario step by step until the intersection saturates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ramp {
+    pub step: f32,
+    pub max_scale: f32,
+    pub seeds: u64,
+    pub duration: f32,
+}
+
+impl Ramp {
+    /// The delay-vs-volume curve of `controller`, ending with the first saturated level.
+    pub fn run(&self, scenario: &Scenario, controller: ControllerKind) -> Vec<CapacityPoint> {
+        let mut points = Vec::new();
+        let mut rate_scale = self.step;
+        while rate_scale <= self.max_scale + 1e-4 {
+            let point = self.measure(scenario, controller, rate_scale);
+            let saturated = point.overall.saturated;
+            points.push(point);
+            if saturated {
+                break;
+            }
+            rate_scale += self.step;
+        }
+        points
+    }
+
+    fn measure(
+        &self,
+        scenario: &Scenario,
+        controller: ControllerKind,
+        rate_scale: f32,
+    ) -> CapacityPoint {
+        let runs: Vec<CapacityPoint> = (0..self.seeds)
+            .into_par_iter()
+            .map(|seed| {
+                let mut scenario = scenario.clone();
+                for demand in &mut scenario.demand {
+                    demand.rate *= rate_scale;
+                }
+                scenario.controller.kind = controller;
+                scenario.seed = scenario.seed.wrapping_add(seed);
+                measure_run(&scenario.run(self.duration), controller, rate_scale)
+            })
+            .collect();
+        average(&runs)
+    }
+}
+
+fn measure_run(
+    intersection: &Intersection,
+    controller: ControllerKind,
+    rate_scale: f32,
+) -> CapacityPoint {
+    let mut statistics = Statistics::new();
+    statistics.update(intersection);
+    let hours = statistics.duration / 3600.0;
+    // A signal queues on red and empties on green, only whole cycles tell
+    // whether the queue grows.
+    let window = match controller {
+        ControllerKind::Signal => {
+            (intersection.controller.cycle() / SERIES_PERIOD).round() as usize
+        }
+        _ => 1,
+    };
+    let approaches: BTreeMap<Approach, Volume> = Approach::ALL
+        .iter()
+        .map(|approach| {
+            let arrivals = intersection.arrivals.get(approach).copied().unwrap_or(0);
+            let passed = statistics
+                .trips
+                .iter()
+                .filter(|t| t.route.approach() == *approach)
+                .count();
+            let queue: Vec<f32> = statistics
+                .series
+                .iter()
+                .map(|p| p.queues.get(approach).copied().unwrap_or(0) as f32)
+                .collect();
+            let volume = Volume {
+                offered: arrivals as f32 / hours,
+                throughput: passed as f32 / hours,
+                saturated: is_growing(&smooth(&queue, window)),
+            };
+            (*approach, volume)
+        })
+        .collect();
+    let overall = statistics.overall();
+    CapacityPoint {
+        controller,
+        rate_scale,
+        overall: Volume {
+            offered: approaches.values().map(|v| v.offered).sum(),
+            throughput: statistics.trips.len() as f32 / hours,
+            saturated: approaches.values().any(|v| v.saturated),
+        },
+        approaches,
+        mean_delay: overall.delay.mean,
+        p95_delay: overall.delay.p95,
+    }
+}
+
+/// Whether a queue sampled over the run keeps growing.
+fn is_growing(queue: &[f32]) -> bool {
+    let quarter = queue.len() / 4;
+    if quarter == 0 {
+        return false;
+    }
+    let mean = |values: &[f32]| values.iter().sum::<f32>() / values.len() as f32;
+    let early = mean(&queue[quarter..2 * quarter]);
+    let late = mean(&queue[queue.len() - quarter..]);
+    late > early * GROWTH_LIMIT + GROWTH_MARGIN
+}
+
+/// Mean of every `window` consecutive samples.
+fn smooth(queue: &[f32], window: usize) -> Vec<f32> {
+    queue
+        .windows(window.max(1))
+        .map(|values| values.iter().sum::<f32>() / values.len() as f32)
+        .collect()
+}
+
+/// Mean over the seeds, a level is saturated when most seeds saturate.
+fn average(runs: &[CapacityPoint]) -> CapacityPoint {
+    let n = runs.len() as f32;
+    let volume = |pick: &dyn Fn(&CapacityPoint) -> Volume| Volume {
+        offered: runs.iter().map(|r| pick(r).offered).sum::<f32>() / n,
+        throughput: runs.iter().map(|r| pick(r).throughput).sum::<f32>() / n,
+        saturated: runs.iter().filter(|r| pick(r).saturated).count() as f32 > n / 2.0,
+    };
+    CapacityPoint {
+        controller: runs[0].controller,
+        rate_scale: runs[0].rate_scale,
+        overall: volume(&|r| r.overall),
+        approaches: Approach::ALL
+            .iter()
+            .map(|a| (*a, volume(&|r| r.approaches[a])))
+            .collect(),
+        mean_delay: runs.iter().map(|r| r.mean_delay).sum::<f32>() / n,
+        p95_delay: runs.iter().map(|r| r.p95_delay).sum::<f32>() / n,
+    }
+}
+
+/// Largest throughput reached without saturating, overall and per approach.
+pub fn max_sustainable(points: &[CapacityPoint]) -> (f32, BTreeMap<Approach, f32>) {
+    let best = |volumes: Vec<Volume>| {
+        volumes
+            .iter()
+            .filter(|v| !v.saturated)
+            .map(|v| v.throughput)
+            .fold(0.0, f32::max)
+    };
+    let overall = best(points.iter().map(|p| p.overall).collect());
+    let approaches = Approach::ALL
+        .iter()
+        .map(|a| (*a, best(points.iter().map(|p| p.approaches[a]).collect())))
+        .collect();
+    (overall, approaches)
+}
+
+/// The curve as CSV, volumes in vehicles per hour.
+pub fn to_csv(points: &[CapacityPoint]) -> String {
+    let mut text =
+        String::from("controller,rate_scale,offered,throughput,mean_delay,p95_delay,saturated");
+    for approach in Approach::ALL {
+        let name = format!("{:?}", approach).to_lowercase();
+        text.push_str(&format!(",{0}_offered,{0}_throughput,{0}_saturated", name));
+    }
+    text.push('\n');
+    for point in points {
+        text.push_str(&format!(
+            "{},{},{:.1},{:.1},{:.4},{:.4},{}",
+            point.controller.name(),
+            point.rate_scale,
+            point.overall.offered,
+            point.overall.throughput,
+            point.mean_delay,
+            point.p95_delay,
+            point.overall.saturated
+        ));
+        for volume in point.approaches.values() {
+            text.push_str(&format!(
+                ",{:.1},{:.1},{}",
+                volume.offered, volume.throughput, volume.saturated
+            ));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+pub fn save(points: &[CapacityPoint], path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, to_csv(points))?;
+    Ok(())
+}
+
+/// The curve of one controller followed by its maximum sustainable throughput.
+pub fn to_table(points: &[CapacityPoint]) -> String {
+    let mut text = format!(
+        "{:>6} {:>10} {:>10} {:>10} {:>10} {:>10}\n",
+        "rate", "offered", "served", "delay s", "p95 s", "saturated"
+    );
+    for point in points {
+        text.push_str(&format!(
+            "{:>6.2} {:>10.0} {:>10.0} {:>10.2} {:>10.2} {:>10}\n",
+            point.rate_scale,
+            point.overall.offered,
+            point.overall.throughput,
+            point.mean_delay,
+            point.p95_delay,
+            if point.overall.saturated { "yes" } else { "" }
+        ));
+    }
+    let (overall, approaches) = max_sustainable(points);
+    text.push_str(&format!(
+        "max sustainable throughput: {:.0} veh/h (",
+        overall
+    ));
+    let approaches: Vec<String> = approaches
+        .iter()
+        .map(|(approach, throughput)| format!("{:?} {:.0}", approach, throughput))
+        .collect();
+    text.push_str(&approaches.join(", "));
+    text.push_str(")\n");
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_steady_queue_is_not_growing() {
+        assert!(!is_growing(&[3.0; 200]));
+        assert!(!is_growing(&[]));
+    }
+
+    #[test]
+    fn a_growing_queue_is_saturated() {
+        let queue: Vec<f32> = (0..200).map(|t| t as f32 / 10.0).collect();
+        assert!(is_growing(&queue));
+    }
+
+    #[test]
+    fn a_signal_cycle_is_smoothed_out() {
+        // Twenty vehicles on red, none on green, with the default 48 s cycle.
+        let cycle = ControllerConfig::default().cycle() as usize;
+        let queue: Vec<f32> = (0..140)
+            .map(|t| if t % cycle >= cycle / 2 { 20.0 } else { 0.0 })
+            .collect();
+        assert!(is_growing(&queue));
+        assert!(!is_growing(&smooth(&queue, cycle)));
+    }
+}
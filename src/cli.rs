@@ -0,0 +1,12 @@
+/// Value following `flag` on the command line, if the flag is present.
+pub fn arg_value(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|a| a == flag)?;
+    match args.get(index + 1) {
+        Some(value) => Some(value.clone()),
+        None => {
+            eprintln!("{} expects a value", flag);
+            std::process::exit(1);
+        }
+    }
+}
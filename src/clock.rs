@@ -0,0 +1,97 @@
+use macroquad::prelude::*;
+use std::time::{Duration, Instant};
+
+/// Available speeds, `None` is "as fast as possible".
+const TIME_SCALES: [Option<f32>; 7] = [
+    Some(0.25),
+    Some(0.5),
+    Some(1.0),
+    Some(2.0),
+    Some(4.0),
+    Some(10.0),
+    None,
+];
+const NORMAL_SCALE: usize = 2;
+const FASTEST_SCALE: usize = TIME_SCALES.len() - 1;
+/// Wall time spent stepping per frame at max speed, leaves room to render.
+const MAX_SPEED_BUDGET: Duration = Duration::from_millis(12);
+
+/// Decides how many simulation ticks run on each rendered frame.
+///
+/// P pauses/resumes, N steps one tick while paused, `-`/`=` change speed.
+pub struct SimClock {
+    pub paused: bool,
+    scale: usize,
+    pending: f32,
+    single_step: bool,
+    started: Instant,
+}
+
+impl Default for SimClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimClock {
+    pub fn new() -> Self {
+        Self {
+            paused: false,
+            scale: NORMAL_SCALE,
+            pending: 0.0,
+            single_step: false,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn handle_keys(&mut self) {
+        if is_key_pressed(KeyCode::P) {
+            self.paused = !self.paused;
+            self.pending = 0.0;
+        }
+        if is_key_pressed(KeyCode::N) && self.paused {
+            self.single_step = true;
+        }
+        if is_key_pressed(KeyCode::Equal) || is_key_pressed(KeyCode::KpAdd) {
+            self.scale = (self.scale + 1).min(FASTEST_SCALE);
+        }
+        if is_key_pressed(KeyCode::Minus) || is_key_pressed(KeyCode::KpSubtract) {
+            self.scale = self.scale.saturating_sub(1);
+        }
+    }
+
+    /// Called once per frame before stepping.
+    pub fn begin_frame(&mut self) {
+        self.started = Instant::now();
+        if !self.paused {
+            if let Some(scale) = TIME_SCALES[self.scale] {
+                self.pending += scale;
+            }
+        }
+    }
+
+    /// Whether one more tick should run in this frame.
+    pub fn tick(&mut self) -> bool {
+        if self.paused {
+            return std::mem::take(&mut self.single_step);
+        }
+        match TIME_SCALES[self.scale] {
+            Some(_) if self.pending >= 1.0 => {
+                self.pending -= 1.0;
+                true
+            }
+            Some(_) => false,
+            None => self.started.elapsed() < MAX_SPEED_BUDGET,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        if self.paused {
+            return "PAUSED (N: step)".to_string();
+        }
+        match TIME_SCALES[self.scale] {
+            Some(scale) => format!("x{}", scale),
+            None => "MAX".to_string(),
+        }
+    }
+}
@@ -2,6 +2,8 @@ use macroquad::prelude::*;
 
 pub const CAR_HEIGHT: f32 = 23.0;
 pub const CAR_WIDTH: f32 = 50.0;
+/// Tint of emergency vehicles over the white car texture.
+pub const EMERGENCY_COLOR: Color = Color::new(1.0, 0.25, 0.25, 1.0);
 
 pub const VITESSE_MIN: f32 = 0.7;
 pub const VITESSE_NORMAL: f32 = 1.5;
@@ -9,4 +11,20 @@ pub const VITESSE_RAPID: f32 = 2.5;
 
 pub const AVANT_INTERSECTION: Vec2 = vec2(250.0, 700.0);
 pub const APRES_INTERSECTION: Vec2 = vec2(400.0, 600.0);
+/// Metres per pixel of the map: a 50 px car is 4.5 m long.
+pub const METRES_PER_PIXEL: f32 = 0.09;
 pub const SECURITY_DISTANCE: f32 = 70.0;
+/// Gap under which two cars on different routes count as a close call.
+pub const CLOSE_CALL_MARGIN: f32 = 10.0;
+/// Seconds ahead of its stop line at which an emergency vehicle starts
+/// holding the conflicting traffic.
+pub const PREEMPTION_HORIZON: f32 = 3.0;
+/// Halvings used to find how far a held vehicle can still drive in a tick.
+pub const GAP_SEARCH_STEPS: u32 = 8;
+
+/// Simulated seconds advanced by one call to `Intersection::step`.
+pub const TICK: f32 = 1.0 / 60.0;
+/// Simulated seconds between two points of the statistics time series.
+pub const SERIES_PERIOD: f32 = 1.0;
+/// Seconds a vehicle must stand still before it counts as a stop.
+pub const MIN_STOP_TIME: f32 = 0.5;
@@ -0,0 +1,174 @@
+use crate::constants::*;
+use crate::route::*;
+use crate::units::pixels_per_tick_squared;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const CYCLIST_COLOR: Color = Color::new(0.1, 0.55, 0.25, 1.0);
+const BIKE_LANE_COLOR: Color = Color::new(0.2, 0.75, 0.3, 0.35);
+/// Pixels from wheel to wheel, and across the handlebar.
+pub const CYCLIST_LENGTH: f32 = 18.0;
+pub const CYCLIST_WIDTH: f32 = 6.0;
+/// Pixels kept to the cyclist ahead.
+const GAP: f32 = 6.0;
+/// Pixels before the stop position where a cyclist decides whether to go on.
+const ZONE: f32 = 60.0;
+/// Pixels a cyclist keeps to the conflict area while waiting, and rides past
+/// it before vehicles turn behind it, so that they are not close calls.
+const STOP_MARGIN: f32 = CLOSE_CALL_MARGIN + 2.0;
+/// In m/s².
+const ACCELERATION: f32 = 1.0;
+
+/// Coordinate across the road of the middle of the bike lane of `approach`,
+/// along the curb on the right of its traffic.
+pub fn bike_lane(approach: Approach) -> f32 {
+    match approach {
+        Approach::North => 363.0,
+        Approach::South => 641.0,
+        Approach::West => 633.0,
+        Approach::East => 360.0,
+    }
+}
+
+/// The route turning right over the bike lane of `approach`.
+pub fn right_turn(approach: Approach) -> Route {
+    match approach {
+        Approach::North => Route::NW,
+        Approach::South => Route::SE,
+        Approach::West => Route::WS,
+        Approach::East => Route::EN,
+    }
+}
+
+/// Where the exit lane of the right turn of `approach` crosses its bike lane.
+pub fn conflict_area(approach: Approach) -> Rect {
+    let lane = bike_lane(approach) - 4.0;
+    match approach {
+        Approach::North => Rect::new(lane, 370.0, 8.0, CAR_HEIGHT),
+        Approach::South => Rect::new(lane, 610.0, 8.0, CAR_HEIGHT),
+        Approach::West => Rect::new(373.5, lane, CAR_HEIGHT, 8.0),
+        Approach::East => Rect::new(613.5, lane, CAR_HEIGHT, 8.0),
+    }
+}
+
+/// Distances along the bike lane of `approach`, from where cyclists enter
+/// the map, at which its conflict area starts and ends.
+fn conflict_span(approach: Approach) -> (f32, f32) {
+    let area = conflict_area(approach);
+    match approach {
+        Approach::North => (area.y, area.bottom()),
+        Approach::South => (1000.0 - area.bottom(), 1000.0 - area.y),
+        Approach::West => (area.x, area.right()),
+        Approach::East => (1000.0 - area.right(), 1000.0 - area.x),
+    }
+}
+
+pub fn draw_bike_lanes() {
+    for approach in Approach::ALL {
+        let lane = bike_lane(approach) - CYCLIST_WIDTH;
+        match approach {
+            Approach::North | Approach::South => {
+                draw_rectangle(lane, 0.0, 2.0 * CYCLIST_WIDTH, 1000.0, BIKE_LANE_COLOR)
+            }
+            Approach::West | Approach::East => {
+                draw_rectangle(0.0, lane, 1000.0, 2.0 * CYCLIST_WIDTH, BIKE_LANE_COLOR)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cyclist {
+    pub id: u32,
+    pub approach: Approach,
+    /// Pixels from the map edge to the front wheel.
+    pub travelled: f32,
+    /// Where the front wheel was at spawn, behind the edge when the lane was full.
+    pub start: f32,
+    /// Pixels per tick.
+    pub speed: f32,
+    pub max_speed: f32,
+    pub spawn_tick: u64,
+    /// When the cyclist reached the zone before the conflict area.
+    pub zone_tick: Option<u64>,
+    /// Going on over the conflict area, vehicles turning right give way.
+    pub committed: bool,
+    pub stopped_ticks: u64,
+}
+
+impl Cyclist {
+    pub fn new(id: u32, approach: Approach, start: f32, max_speed: f32, tick: u64) -> Cyclist {
+        Cyclist {
+            id,
+            approach,
+            travelled: start,
+            start,
+            speed: max_speed,
+            max_speed,
+            spawn_tick: tick,
+            zone_tick: None,
+            committed: false,
+            stopped_ticks: 0,
+        }
+    }
+
+    /// Where the front wheel stops while the cyclist is not going on.
+    pub fn stop_position(&self) -> f32 {
+        conflict_span(self.approach).0 - STOP_MARGIN
+    }
+
+    pub fn in_zone(&self) -> bool {
+        self.travelled >= self.stop_position() - ZONE
+    }
+
+    pub fn has_passed(&self) -> bool {
+        self.travelled - CYCLIST_LENGTH > conflict_span(self.approach).1 + STOP_MARGIN
+    }
+
+    /// Farthest the front wheel of the cyclist behind may go.
+    pub fn room_behind(&self) -> f32 {
+        self.travelled - CYCLIST_LENGTH - GAP
+    }
+
+    pub fn is_out_of_road(&self) -> bool {
+        self.travelled - CYCLIST_LENGTH > 1000.0
+    }
+
+    pub fn body(&self) -> Rect {
+        let lane = bike_lane(self.approach) - CYCLIST_WIDTH / 2.0;
+        let rear = self.travelled - CYCLIST_LENGTH;
+        match self.approach {
+            Approach::North => Rect::new(lane, rear, CYCLIST_WIDTH, CYCLIST_LENGTH),
+            Approach::South => {
+                Rect::new(lane, 1000.0 - self.travelled, CYCLIST_WIDTH, CYCLIST_LENGTH)
+            }
+            Approach::West => Rect::new(rear, lane, CYCLIST_LENGTH, CYCLIST_WIDTH),
+            Approach::East => {
+                Rect::new(1000.0 - self.travelled, lane, CYCLIST_LENGTH, CYCLIST_WIDTH)
+            }
+        }
+    }
+
+    /// Pedals towards the maximum speed without the front wheel going past `limit`.
+    pub fn ride(&mut self, limit: f32) {
+        let speed = (self.speed + pixels_per_tick_squared(ACCELERATION)).min(self.max_speed);
+        let next = (self.travelled + speed).min(limit).max(self.travelled);
+        self.speed = next - self.travelled;
+        self.travelled = next;
+        if self.speed <= 0.0 {
+            self.stopped_ticks += 1;
+        }
+    }
+
+    /// Seconds to ride the whole lane from the start without stopping, in
+    /// whole ticks as travel times are.
+    pub fn free_flow_time(&self) -> f32 {
+        ((1000.0 + CYCLIST_LENGTH - self.start) / self.max_speed).floor() * TICK
+    }
+
+    pub fn draw(&self) {
+        let body = self.body();
+        draw_rectangle(body.x, body.y, body.w, body.h, CYCLIST_COLOR);
+        draw_rectangle_lines(body.x, body.y, body.w, body.h, 1.0, DARKGRAY);
+    }
+}
@@ -0,0 +1,365 @@
+use crate::route::Approach;
+use crate::statistics::*;
+use crate::vehicule::VehicleType;
+use serde::Serialize;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Everything `summary.json` holds.
+#[derive(Serialize)]
+struct Export<'a> {
+    summary: Summary,
+    overall: GroupStatistics,
+    routes: &'a [GroupStatistics],
+    approaches: &'a [GroupStatistics],
+    kinds: &'a [GroupStatistics],
+    drivers: &'a [GroupStatistics],
+    trips: &'a [Trip],
+    pedestrians: &'a [PedestrianTrip],
+    cyclists: &'a [CyclistTrip],
+}
+
+/// Writes `summary.json`, `groups.csv`, `trips.csv`, `pedestrians.csv`,
+/// `cyclists.csv`, `series.csv` and `report.txt` into `dir`, replacing the
+/// files of a previous export.
+pub fn write(statistics: &Statistics, dir: &str) -> Result<(), Box<dyn Error>> {
+    let dir = Path::new(dir);
+    fs::create_dir_all(dir)?;
+    let overall = statistics.overall();
+    let export = Export {
+        summary: statistics.summary(),
+        overall: overall.clone(),
+        routes: &statistics.routes,
+        approaches: &statistics.approaches,
+        kinds: &statistics.kinds,
+        drivers: &statistics.drivers,
+        trips: &statistics.trips,
+        pedestrians: &statistics.pedestrians,
+        cyclists: &statistics.cyclists,
+    };
+    fs::write(
+        dir.join("summary.json"),
+        serde_json::to_string_pretty(&export)?,
+    )?;
+
+    let mut groups = String::from("group,vehicles,mean_travel_time");
+    for measure in ["delay", "stops", "stopped_time", "average_speed"] {
+        for column in ["mean", "p50", "p95", "max"] {
+            groups.push_str(&format!(",{}_{}", measure, column));
+        }
+    }
+    groups.push_str(",level_of_service\n");
+    for group in statistics
+        .routes
+        .iter()
+        .chain(&statistics.approaches)
+        .chain(&statistics.kinds)
+        .chain(&statistics.drivers)
+        .chain(std::iter::once(&overall))
+    {
+        groups.push_str(&format!(
+            "{},{},{:.4}",
+            group.name, group.vehicles, group.travel_time.mean
+        ));
+        for measure in [group.delay, group.stops, group.stopped_time, group.speed] {
+            groups.push_str(&format!(
+                ",{:.4},{:.4},{:.4},{:.4}",
+                measure.mean, measure.p50, measure.p95, measure.max
+            ));
+        }
+        groups.push_str(&format!(",{}\n", los_label(group.level_of_service)));
+    }
+    fs::write(dir.join("groups.csv"), groups)?;
+
+    let mut trips = String::from(
+        "id,route,vehicle_type,spawn_time,entry_time,exit_time,travel_time,free_flow_time,delay,min_gap,stops,stopped_time,average_speed,max_speed,held_time,driver\n",
+    );
+    for trip in &statistics.trips {
+        trips.push_str(&format!(
+            "{},{:?},{},{:.4},{:.4},{:.4},{:.4},{:.4},{:.4},{},{},{:.4},{:.4},{:.4},{:.4},{}\n",
+            trip.id,
+            trip.route,
+            trip.kind.name(),
+            trip.spawn_time,
+            trip.entry_time,
+            trip.exit_time,
+            trip.travel_time(),
+            trip.free_flow_time,
+            trip.delay,
+            trip.min_gap
+                .map(|g| format!("{:.2}", g))
+                .unwrap_or_default(),
+            trip.stops,
+            trip.stopped_time,
+            trip.average_speed,
+            trip.max_speed,
+            trip.held_time,
+            if trip.human { "human" } else { "autonomous" }
+        ));
+    }
+    fs::write(dir.join("trips.csv"), trips)?;
+
+    let mut pedestrians = String::from("id,crosswalk,arrival_time,start_time,exit_time,wait\n");
+    for person in &statistics.pedestrians {
+        pedestrians.push_str(&format!(
+            "{},{:?},{:.4},{:.4},{:.4},{:.4}\n",
+            person.id,
+            person.crosswalk,
+            person.arrival_time,
+            person.start_time,
+            person.exit_time,
+            person.wait()
+        ));
+    }
+    fs::write(dir.join("pedestrians.csv"), pedestrians)?;
+
+    let mut cyclists = String::from(
+        "id,approach,spawn_time,exit_time,travel_time,free_flow_time,delay,stopped_time\n",
+    );
+    for rider in &statistics.cyclists {
+        cyclists.push_str(&format!(
+            "{},{:?},{:.4},{:.4},{:.4},{:.4},{:.4},{:.4}\n",
+            rider.id,
+            rider.approach,
+            rider.spawn_time,
+            rider.exit_time,
+            rider.travel_time(),
+            rider.free_flow_time,
+            rider.delay,
+            rider.stopped_time
+        ));
+    }
+    fs::write(dir.join("cyclists.csv"), cyclists)?;
+
+    let mut series = String::from("time,passed,in_system");
+    for approach in Approach::ALL {
+        series.push_str(&format!(",queue_{:?}", approach).to_lowercase());
+    }
+    series.push('\n');
+    for point in &statistics.series {
+        series.push_str(&format!(
+            "{:.2},{},{}",
+            point.time, point.passed, point.in_system
+        ));
+        for approach in Approach::ALL {
+            series.push_str(&format!(
+                ",{}",
+                point.queues.get(&approach).copied().unwrap_or(0)
+            ));
+        }
+        series.push('\n');
+    }
+    fs::write(dir.join("series.csv"), series)?;
+    fs::write(dir.join("report.txt"), text_report(statistics))?;
+    Ok(())
+}
+
+/// The report as plain text, for terminals and CI logs.
+pub fn text_report(statistics: &Statistics) -> String {
+    let summary = statistics.summary();
+    let overall = statistics.overall();
+    let mut text = format!(
+        "Duration: {:.1} s\n\
+         Vehicles passed: {}\n\
+         Throughput: {:.1} veh/min\n\
+         Mean travel time: {:.2} s\n\
+         Delay: mean {:.2} s, p50 {:.2} s, p95 {:.2} s\n\
+         Stops per vehicle: {:.2}, stopped {:.2} s\n\
+         Average speed: {:.1} km/h, max {:.1} km/h\n\
+         Level of service: {}\n\
+         Close calls: {}\n\
+         Collisions: {}\n",
+        summary.duration,
+        summary.passed,
+        summary.throughput,
+        summary.mean_travel_time,
+        overall.delay.mean,
+        overall.delay.p50,
+        overall.delay.p95,
+        overall.stops.mean,
+        overall.stopped_time.mean,
+        overall.speed.mean * 3.6,
+        statistics.max_speed * 3.6,
+        los_label(summary.level_of_service),
+        summary.close_calls,
+        summary.collisions
+    );
+    if summary.emergency_vehicles > 0 {
+        let emergency = statistics.kind(VehicleType::Emergency).unwrap();
+        text.push_str(&format!(
+            "Emergency vehicles: {}, delay mean {:.2} s, p95 {:.2} s\n\
+             Held for them: {} vehicles, {:.1} s in total\n",
+            summary.emergency_vehicles,
+            emergency.delay.mean,
+            emergency.delay.p95,
+            summary.vehicles_held,
+            summary.held_time
+        ));
+    }
+    if summary.pedestrians > 0 {
+        text.push_str(&format!(
+            "Pedestrians: {}, wait mean {:.2} s, p95 {:.2} s, max {:.2} s\n\
+             Vehicles over a crosswalk: {} close calls, {} collisions with pedestrians\n",
+            summary.pedestrians,
+            statistics.pedestrian_wait.mean,
+            statistics.pedestrian_wait.p95,
+            statistics.pedestrian_wait.max,
+            summary.pedestrian_close_calls,
+            summary.pedestrian_collisions
+        ));
+    }
+    if summary.cyclists > 0 {
+        text.push_str(&format!(
+            "Cyclists: {}, delay mean {:.2} s, p95 {:.2} s, max {:.2} s\n\
+             Right turns over a bike lane: {} close calls, {} collisions with cyclists\n",
+            summary.cyclists,
+            statistics.cyclist_delay.mean,
+            statistics.cyclist_delay.p95,
+            statistics.cyclist_delay.max,
+            summary.cyclist_close_calls,
+            summary.cyclist_collisions
+        ));
+    }
+    if summary.human_vehicles > 0 {
+        let human = statistics.driver(true).unwrap();
+        text.push_str(&format!(
+            "Human-driven vehicles: {} ({:.0} %), delay mean {:.2} s, p95 {:.2} s\n",
+            summary.human_vehicles,
+            summary.human_vehicles as f32 / statistics.trips.len() as f32 * 100.0,
+            human.delay.mean,
+            human.delay.p95
+        ));
+    }
+    text.push('\n');
+    // Per vehicle type and per driver only when there is more than one.
+    let mixed = statistics.kinds.iter().filter(|g| g.vehicles > 0).count() > 1;
+    let drivers = statistics.drivers.iter().all(|g| g.vehicles > 0);
+    text.push_str(&format!(
+        "{:<10} {:>8} {:>10} {:>10} {:>10} {:>10} {:>6} {:>4}\n",
+        "group", "vehicles", "travel s", "delay s", "p50 s", "p95 s", "stops", "LOS"
+    ));
+    for group in statistics
+        .approaches
+        .iter()
+        .chain(&statistics.routes)
+        .chain(statistics.kinds.iter().filter(|g| mixed && g.vehicles > 0))
+        .chain(statistics.drivers.iter().filter(|_| drivers))
+        .chain(std::iter::once(&overall))
+    {
+        text.push_str(&format!(
+            "{:<10} {:>8} {:>10.2} {:>10.2} {:>10.2} {:>10.2} {:>6.2} {:>4}\n",
+            group.name,
+            group.vehicles,
+            group.travel_time.mean,
+            group.delay.mean,
+            group.delay.p50,
+            group.delay.p95,
+            group.stops.mean,
+            los_label(group.level_of_service)
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::route::Route;
+    use crate::scenario::{CyclistDemand, PedestrianDemand};
+    use crate::test_util::{scenario, temp_path};
+
+    /// Header and rows of a CSV file, checking every row has a field per column.
+    fn table(dir: &Path, name: &str) -> (Vec<String>, Vec<Vec<String>>) {
+        let text = fs::read_to_string(dir.join(name)).unwrap();
+        let mut lines = text
+            .lines()
+            .map(|l| l.split(',').map(String::from).collect());
+        let header: Vec<String> = lines.next().unwrap();
+        let rows: Vec<Vec<String>> = lines.collect();
+        for row in &rows {
+            assert_eq!(row.len(), header.len(), "{}: {:?}", name, row);
+        }
+        (header, rows)
+    }
+
+    #[test]
+    fn files_have_their_documented_columns() {
+        let mut scenario = scenario(5);
+        scenario.pedestrians.push(PedestrianDemand {
+            crosswalk: Approach::North,
+            rate: 6.0,
+            speed: 1.3,
+        });
+        scenario.cyclists.push(CyclistDemand {
+            approach: Approach::South,
+            rate: 6.0,
+            speed: 4.0,
+        });
+        let mut statistics = Statistics::new();
+        statistics.update(&scenario.run(120.0));
+        let dir = temp_path("export");
+        write(&statistics, &dir).unwrap();
+        let dir = Path::new(&dir);
+
+        let (header, rows) = table(dir, "groups.csv");
+        assert_eq!(header.len(), 3 + 4 * 4 + 1);
+        assert_eq!(header[3], "delay_mean");
+        assert_eq!(header[18], "average_speed_max");
+        assert_eq!(header[19], "level_of_service");
+        let groups = Route::ALL.len() + Approach::ALL.len() + VehicleType::ALL.len() + 2 + 1;
+        assert_eq!(rows.len(), groups);
+        assert_eq!(rows[groups - 1][0], "All");
+
+        let (header, rows) = table(dir, "trips.csv");
+        assert_eq!(header[0], "id");
+        assert_eq!(header[15], "driver");
+        assert_eq!(rows.len(), statistics.trips.len());
+        assert!(!rows.is_empty());
+
+        let (header, rows) = table(dir, "pedestrians.csv");
+        assert_eq!(
+            header,
+            [
+                "id",
+                "crosswalk",
+                "arrival_time",
+                "start_time",
+                "exit_time",
+                "wait"
+            ]
+        );
+        assert_eq!(rows.len(), statistics.pedestrians.len());
+
+        let (header, rows) = table(dir, "cyclists.csv");
+        assert_eq!(header[6], "delay");
+        assert_eq!(rows.len(), statistics.cyclists.len());
+
+        let (header, rows) = table(dir, "series.csv");
+        assert_eq!(
+            &header[3..],
+            ["queue_north", "queue_south", "queue_west", "queue_east"]
+        );
+        assert_eq!(rows.len(), statistics.series.len());
+
+        let json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("summary.json")).unwrap()).unwrap();
+        for key in [
+            "summary",
+            "overall",
+            "routes",
+            "approaches",
+            "kinds",
+            "drivers",
+            "trips",
+        ] {
+            assert!(json.get(key).is_some(), "{}", key);
+        }
+        assert_eq!(
+            json["trips"].as_array().unwrap().len(),
+            statistics.trips.len()
+        );
+        assert_eq!(json["summary"]["passed"], statistics.summary().passed);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
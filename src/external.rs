@@ -0,0 +1,231 @@
+use crate::constants::*;
+use crate::intersection::*;
+use crate::route::Route;
+use crate::units::*;
+use crate::vehicule::*;
+use macroquad::prelude::vec2;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+
+/// One vehicle as sent to an external controller, distances in metres and
+/// speeds in metres per second.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VehicleObservation {
+    pub id: u32,
+    pub route: Route,
+    pub kind: VehicleType,
+    pub length: f32,
+    pub x: f32,
+    pub y: f32,
+    pub speed: f32,
+    pub max_speed: f32,
+    /// Distance left to the stop line, negative once past it.
+    pub distance_to_stop_line: f32,
+    pub state: ControlState,
+    pub granted: bool,
+    /// Human drivers ignore grants and speed commands.
+    pub human: bool,
+}
+
+/// Everything sent on one tick.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    pub tick: u64,
+    pub time: f32,
+    pub vehicles: Vec<VehicleObservation>,
+}
+
+impl Observation {
+    pub fn of(intersection: &Intersection) -> Observation {
+        let mut vehicles: Vec<VehicleObservation> = intersection
+            .vehicules()
+            .into_iter()
+            .map(|car| {
+                let centre = car.coordonne + vec2(CAR_WIDTH, CAR_HEIGHT) / 2.0;
+                VehicleObservation {
+                    id: car.id,
+                    route: car.route,
+                    kind: car.kind,
+                    length: metres(car.kind.spec().length),
+                    x: metres(centre.x),
+                    y: metres(centre.y),
+                    speed: metres_per_second(car.speed()),
+                    max_speed: metres_per_second(car.vitesse_max),
+                    distance_to_stop_line: metres(car.distance_to_stop()),
+                    state: intersection.control_state(car),
+                    granted: intersection.grants.contains(&car.id),
+                    human: car.human.is_some(),
+                }
+            })
+            .collect();
+        vehicles.sort_by_key(|v| v.id);
+        Observation {
+            tick: intersection.tick,
+            time: intersection.time(),
+            vehicles,
+        }
+    }
+}
+
+/// The answer to an observation. Grants last until the vehicle leaves,
+/// speeds in km/h until replaced, `null` hands the vehicle back to its
+/// approach speed.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Command {
+    #[serde(default)]
+    pub grant: Vec<u32>,
+    #[serde(default)]
+    pub speed: BTreeMap<u32, Option<f32>>,
+}
+
+impl Command {
+    /// Applies the command, ids of vehicles no longer in the intersection are ignored.
+    pub fn apply(&self, intersection: &mut Intersection) {
+        for id in &self.grant {
+            if intersection.vehicules().iter().any(|car| car.id == *id) {
+                intersection.grants.insert(*id);
+            }
+        }
+        for (id, speed) in &self.speed {
+            // Capped at the approach speed: a car at its top speed is taken
+            // for one already cleared to cross.
+            let Some(approche) = intersection
+                .vehicules()
+                .iter()
+                .find(|car| car.id == *id)
+                .map(|car| {
+                    let (x, y) = car.route.get_speed();
+                    x.abs().max(y.abs())
+                })
+            else {
+                continue;
+            };
+            match speed {
+                Some(kmh) => {
+                    let vitesse = pixels_per_tick(*kmh).clamp(0.0, approche);
+                    intersection.speed_commands.insert(*id, vitesse);
+                }
+                None => {
+                    intersection.speed_commands.remove(id);
+                }
+            }
+        }
+    }
+}
+
+/// A controller in another process, talking line-delimited JSON: one
+/// `Observation` is written per tick and one `Command` is read back.
+pub struct Connection {
+    reader: Box<dyn BufRead>,
+    writer: Box<dyn Write>,
+}
+
+impl Connection {
+    /// Waits for one client on `tcp:<host>:<port>` or `unix:<path>`.
+    pub fn listen(address: &str) -> Result<Connection, Box<dyn Error>> {
+        if let Some(path) = address.strip_prefix("unix:") {
+            return listen_unix(path);
+        }
+        let address = address.strip_prefix("tcp:").unwrap_or(address);
+        let (stream, _) = TcpListener::bind(address)?.accept()?;
+        stream.set_nodelay(true)?;
+        Ok(Connection {
+            reader: Box::new(BufReader::new(stream.try_clone()?)),
+            writer: Box::new(stream),
+        })
+    }
+
+    /// Sends the current state and applies the answer.
+    pub fn exchange(&mut self, intersection: &mut Intersection) -> Result<(), Box<dyn Error>> {
+        let observation = Observation::of(intersection);
+        self.send(&observation)?;
+        let Some(command) = self.receive::<Command>()? else {
+            return Err("controller disconnected".into());
+        };
+        command.apply(intersection);
+        Ok(())
+    }
+
+    pub fn send<T: Serialize>(&mut self, message: &T) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(&mut self.writer, message)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// The next message, `None` once the client has closed the connection.
+    pub fn receive<T: for<'a> Deserialize<'a>>(&mut self) -> Result<Option<T>, Box<dyn Error>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&line)?))
+    }
+}
+
+#[cfg(unix)]
+fn listen_unix(path: &str) -> Result<Connection, Box<dyn Error>> {
+    use std::os::unix::fs::FileTypeExt;
+    use std::os::unix::net::UnixListener;
+
+    // A socket left behind by a previous run would make bind fail.
+    if std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(path)?;
+    }
+    let (stream, _) = UnixListener::bind(path)?.accept()?;
+    Ok(Connection {
+        reader: Box::new(BufReader::new(stream.try_clone()?)),
+        writer: Box::new(stream),
+    })
+}
+
+#[cfg(not(unix))]
+fn listen_unix(_path: &str) -> Result<Connection, Box<dyn Error>> {
+    Err("unix sockets are not available on this platform".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::{ControllerConfig, ControllerKind, Layout};
+
+    fn external() -> Intersection {
+        let controller = ControllerConfig {
+            kind: ControllerKind::External,
+            ..Default::default()
+        };
+        Intersection::with_config(controller, Layout::default(), 0)
+    }
+
+    #[test]
+    fn a_speed_command_is_not_a_grant() {
+        let mut intersection = external();
+        intersection.add_car(vec![Route::NS], VehicleType::Car);
+        let leader = intersection.vehicules()[0].id;
+        // The leader is granted and keeps its lane occupied while the
+        // follower arrives at the crossing speed, far above the cap.
+        let mut command = Command {
+            grant: vec![leader],
+            ..Default::default()
+        };
+        intersection.add_car(vec![Route::NS], VehicleType::Car);
+        while intersection.time() < 15.0 {
+            for car in intersection.vehicules() {
+                if car.id != leader {
+                    command.speed.insert(car.id, Some(1000.0));
+                }
+            }
+            command.apply(&mut intersection);
+            intersection.step();
+        }
+        let cars = intersection.vehicules();
+        assert_eq!(cars.len(), 1, "the leader should have left");
+        let car = cars[0];
+        assert_ne!(car.id, leader);
+        assert!(!car.in_stop_zone(), "crossed without a grant");
+        assert_eq!(intersection.control_state(car), ControlState::Waiting);
+    }
+}
@@ -0,0 +1,414 @@
+use crate::constants::*;
+use crate::external::{self, Command, Connection};
+use crate::intersection::*;
+use crate::route::Route;
+use crate::scenario::*;
+use crate::vehicule::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// Columns of a vehicle row in the `features` encoding.
+pub const FEATURES: [&str; 9] = [
+    "present",
+    "route",
+    "length",
+    "distance_to_stop_line",
+    "speed",
+    "max_speed",
+    "waiting",
+    "granted",
+    "human",
+];
+
+/// How the state is turned into the flat vector a learner sees.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Encoding {
+    /// Vehicles per cell, one row per lane in `Route::ALL` order: `cells`
+    /// cells from the lane's start to its stop line, then one for the
+    /// intersection itself.
+    Grid { cells: usize },
+    /// One `FEATURES` row per vehicle, the ones still approaching first,
+    /// padded with zeros to `max_vehicles` rows.
+    Features { max_vehicles: usize },
+}
+
+impl Default for Encoding {
+    fn default() -> Encoding {
+        Encoding::Grid { cells: 20 }
+    }
+}
+
+/// Weights of the reward given after each step.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Reward {
+    /// Per second of delay, summed over the vehicles in the system.
+    pub delay: f32,
+    /// Per new collision.
+    pub collision: f32,
+}
+
+impl Default for Reward {
+    fn default() -> Reward {
+        Reward {
+            delay: 1.0,
+            collision: 100.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EnvConfig {
+    pub encoding: Encoding,
+    pub reward: Reward,
+    /// Simulated seconds between two actions.
+    pub decision_period: f32,
+    /// Episode length in simulated seconds.
+    pub duration: f32,
+}
+
+impl Default for EnvConfig {
+    fn default() -> EnvConfig {
+        EnvConfig {
+            encoding: Encoding::default(),
+            reward: Reward::default(),
+            decision_period: 0.1,
+            duration: 300.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Observation {
+    /// Vehicles as sent to an external controller, to address them in actions.
+    pub state: external::Observation,
+    pub shape: Vec<usize>,
+    pub values: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Info {
+    /// Vehicle-seconds lost during the step.
+    pub delay: f32,
+    pub collisions: u32,
+    pub passed: u32,
+    pub in_system: usize,
+    pub time: f32,
+}
+
+/// What `step` returns.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transition {
+    pub observation: Observation,
+    pub reward: f32,
+    pub done: bool,
+    pub info: Info,
+}
+
+/// A scenario as a reinforcement-learning environment, the agent acting as
+/// an external controller.
+pub struct Environment {
+    pub scenario: Scenario,
+    pub config: EnvConfig,
+    pub intersection: Intersection,
+}
+
+impl Environment {
+    pub fn new(scenario: Scenario, config: EnvConfig) -> Environment {
+        let mut environment = Environment {
+            intersection: Intersection::new(),
+            scenario,
+            config,
+        };
+        let seed = environment.scenario.seed;
+        environment.reset(seed);
+        environment
+    }
+
+    /// Starts a new episode with `seed`.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.scenario.seed = seed;
+        self.intersection = self.scenario.build();
+        self.intersection.controller.kind = ControllerKind::External;
+        self.observe()
+    }
+
+    /// Applies `action`, then runs the simulation for one decision period.
+    pub fn step(&mut self, action: &Command) -> Transition {
+        action.apply(&mut self.intersection);
+        let collisions = self.intersection.collisions;
+        let ticks = ((self.config.decision_period / TICK).round() as u64).max(1);
+        let mut delay = 0.0;
+        for _ in 0..ticks {
+            if self.is_done() {
+                break;
+            }
+            self.scenario.spawn(&mut self.intersection);
+            self.intersection.step();
+            delay += self.lost_time();
+        }
+        let collisions = self.intersection.collisions - collisions;
+        let reward =
+            -self.config.reward.delay * delay - self.config.reward.collision * collisions as f32;
+        Transition {
+            observation: self.observe(),
+            reward,
+            done: self.is_done(),
+            info: Info {
+                delay,
+                collisions,
+                passed: self.intersection.number_of_passed_vehicles,
+                in_system: self.intersection.vehicules().len()
+                    + self.intersection.waiting_to_enter(),
+                time: self.intersection.time(),
+            },
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.intersection.time() >= self.config.duration
+    }
+
+    /// Seconds lost on the last tick: the shortfall from full speed of every
+    /// vehicle, and the whole tick for those waiting to enter.
+    fn lost_time(&self) -> f32 {
+        let driving: f32 = self
+            .intersection
+            .vehicules()
+            .iter()
+            .map(|car| (1.0 - car.speed() / car.vitesse_max).max(0.0))
+            .sum();
+        (driving + self.intersection.waiting_to_enter() as f32) * TICK
+    }
+
+    pub fn observe(&self) -> Observation {
+        let state = external::Observation::of(&self.intersection);
+        let (shape, values) = match self.config.encoding {
+            Encoding::Grid { cells } => {
+                let cells = cells.max(1);
+                (vec![Route::ALL.len(), cells + 1], self.grid(cells))
+            }
+            Encoding::Features { max_vehicles } => (
+                vec![max_vehicles, FEATURES.len()],
+                features(&state, max_vehicles),
+            ),
+        };
+        Observation {
+            state,
+            shape,
+            values,
+        }
+    }
+
+    fn grid(&self, cells: usize) -> Vec<f32> {
+        let width = cells + 1;
+        let mut values = vec![0.0; Route::ALL.len() * width];
+        for car in self.intersection.vehicules() {
+            if car.after_intersect() {
+                continue;
+            }
+            let Some(lane) = Route::ALL.iter().position(|r| *r == car.route) else {
+                continue;
+            };
+            let length = Vehicule::spawn(car.route, car.kind, 0).distance_to_stop();
+            let distance = car.distance_to_stop();
+            let cell = if distance <= 0.0 {
+                cells
+            } else {
+                (((1.0 - distance / length) * cells as f32) as usize).min(cells - 1)
+            };
+            values[lane * width + cell] += 1.0;
+        }
+        values
+    }
+}
+
+fn features(state: &external::Observation, max_vehicles: usize) -> Vec<f32> {
+    let mut vehicles: Vec<&external::VehicleObservation> = state.vehicles.iter().collect();
+    // Vehicles still approaching first, closest to their stop line first.
+    vehicles.sort_by(|a, b| {
+        let key = |v: &external::VehicleObservation| {
+            (v.distance_to_stop_line < 0.0, v.distance_to_stop_line.abs())
+        };
+        let (a, b) = (key(a), key(b));
+        a.0.cmp(&b.0).then(a.1.total_cmp(&b.1))
+    });
+    let mut values = Vec::with_capacity(max_vehicles * FEATURES.len());
+    for vehicle in vehicles.iter().take(max_vehicles) {
+        let route = Route::ALL
+            .iter()
+            .position(|r| *r == vehicle.route)
+            .unwrap_or(0);
+        values.extend([
+            1.0,
+            route as f32,
+            vehicle.length,
+            vehicle.distance_to_stop_line,
+            vehicle.speed,
+            vehicle.max_speed,
+            (vehicle.state == ControlState::Waiting) as u8 as f32,
+            vehicle.granted as u8 as f32,
+            vehicle.human as u8 as f32,
+        ]);
+    }
+    values.resize(max_vehicles * FEATURES.len(), 0.0);
+    values
+}
+
+/// A message from a learner connected with `--gym`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Request {
+    /// Answered with an `Observation`. A `config` replaces the current one.
+    Reset {
+        seed: u64,
+        #[serde(default)]
+        config: Option<EnvConfig>,
+    },
+    /// Answered with a `Transition`.
+    Step(Command),
+    Close,
+}
+
+/// Answers the requests of one learner until it closes the connection.
+pub fn serve(
+    environment: &mut Environment,
+    connection: &mut Connection,
+) -> Result<(), Box<dyn Error>> {
+    loop {
+        let Some(request) = connection.receive::<Request>()? else {
+            return Ok(());
+        };
+        match request {
+            Request::Reset { seed, config } => {
+                if let Some(config) = config {
+                    environment.config = config;
+                }
+                let observation = environment.reset(seed);
+                connection.send(&observation)?;
+            }
+            Request::Step(action) => {
+                let transition = environment.step(&action);
+                connection.send(&transition)?;
+            }
+            Request::Close => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scenario;
+
+    fn environment(encoding: Encoding) -> Environment {
+        let config = EnvConfig {
+            encoding,
+            decision_period: 5.0,
+            ..Default::default()
+        };
+        Environment::new(scenario(5), config)
+    }
+
+    fn vehicle(id: u32, distance_to_stop_line: f32) -> external::VehicleObservation {
+        external::VehicleObservation {
+            id,
+            route: Route::WE,
+            kind: VehicleType::Car,
+            length: 50.0,
+            x: 0.0,
+            y: 0.0,
+            speed: 1.5,
+            max_speed: 2.5,
+            distance_to_stop_line,
+            state: ControlState::Waiting,
+            granted: id == 3,
+            human: id == 2,
+        }
+    }
+
+    fn column(row: &[f32], name: &str) -> f32 {
+        row[FEATURES.iter().position(|f| *f == name).unwrap()]
+    }
+
+    #[test]
+    fn features_put_approaching_vehicles_first_and_pad() {
+        let state = external::Observation {
+            tick: 0,
+            time: 0.0,
+            vehicles: vec![vehicle(1, -20.0), vehicle(2, 80.0), vehicle(3, 10.0)],
+        };
+        let values = features(&state, 4);
+        assert_eq!(values.len(), 4 * FEATURES.len());
+        let rows: Vec<&[f32]> = values.chunks(FEATURES.len()).collect();
+        let distances: Vec<f32> = rows
+            .iter()
+            .map(|row| column(row, "distance_to_stop_line"))
+            .collect();
+        assert_eq!(distances, vec![10.0, 80.0, -20.0, 0.0]);
+        let route = Route::ALL.iter().position(|r| *r == Route::WE).unwrap();
+        assert_eq!(column(rows[0], "present"), 1.0);
+        assert_eq!(column(rows[0], "route"), route as f32);
+        assert_eq!(column(rows[0], "length"), 50.0);
+        assert_eq!(column(rows[0], "speed"), 1.5);
+        assert_eq!(column(rows[0], "max_speed"), 2.5);
+        assert_eq!(column(rows[0], "waiting"), 1.0);
+        assert_eq!(column(rows[0], "granted"), 1.0);
+        assert_eq!(column(rows[0], "human"), 0.0);
+        assert_eq!(column(rows[1], "granted"), 0.0);
+        assert_eq!(column(rows[1], "human"), 1.0);
+        assert!(rows[3].iter().all(|v| *v == 0.0));
+    }
+
+    #[test]
+    fn features_keep_the_closest_vehicles() {
+        let state = external::Observation {
+            tick: 0,
+            time: 0.0,
+            vehicles: vec![vehicle(1, 90.0), vehicle(2, 30.0), vehicle(3, 60.0)],
+        };
+        let values = features(&state, 2);
+        let rows: Vec<&[f32]> = values.chunks(FEATURES.len()).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(column(rows[0], "distance_to_stop_line"), 30.0);
+        assert_eq!(column(rows[1], "distance_to_stop_line"), 60.0);
+    }
+
+    #[test]
+    fn features_observation_matches_the_vehicles() {
+        let mut environment = environment(Encoding::Features { max_vehicles: 16 });
+        let observation = environment.step(&Command::default()).observation;
+        assert_eq!(observation.shape, vec![16, FEATURES.len()]);
+        assert_eq!(observation.values.len(), 16 * FEATURES.len());
+        let present: f32 = observation.values.iter().step_by(FEATURES.len()).sum();
+        assert_eq!(present as usize, observation.state.vehicles.len().min(16));
+        assert!(present > 0.0);
+    }
+
+    #[test]
+    fn grid_counts_every_vehicle_before_the_exit() {
+        let mut environment = environment(Encoding::Grid { cells: 10 });
+        let observation = environment.step(&Command::default()).observation;
+        assert_eq!(observation.shape, vec![Route::ALL.len(), 11]);
+        assert_eq!(observation.values.len(), Route::ALL.len() * 11);
+        let expected = environment
+            .intersection
+            .vehicules()
+            .iter()
+            .filter(|car| !car.after_intersect())
+            .count();
+        assert!(expected > 0);
+        assert_eq!(observation.values.iter().sum::<f32>() as usize, expected);
+    }
+
+    #[test]
+    fn encodings_read_from_json() {
+        let grid: Encoding = serde_json::from_str(r#"{"type": "grid", "cells": 8}"#).unwrap();
+        assert_eq!(grid, Encoding::Grid { cells: 8 });
+        let features: Encoding =
+            serde_json::from_str(r#"{"type": "features", "max_vehicles": 12}"#).unwrap();
+        assert_eq!(features, Encoding::Features { max_vehicles: 12 });
+    }
+}
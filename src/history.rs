@@ -0,0 +1,77 @@
+use crate::constants::*;
+use crate::intersection::Intersection;
+use std::collections::VecDeque;
+use std::mem;
+
+/// Lengths of the records a run only ever appends to. States keep these
+/// instead of copies, the records are cut back from the present on rewind.
+#[derive(Debug, Clone, Copy)]
+struct Records {
+    trips: usize,
+    pedestrian_trips: usize,
+    cyclist_trips: usize,
+    series: usize,
+}
+
+/// Ring buffer of recent simulation states for rewinding the viewer.
+pub struct History {
+    states: VecDeque<(Intersection, Records)>,
+    interval: u64,
+    capacity: usize,
+}
+
+impl History {
+    /// Keeps one state every `interval` seconds over the last `span` seconds.
+    pub fn new(interval: f32, span: f32) -> History {
+        History {
+            states: VecDeque::new(),
+            interval: ((interval / TICK).round() as u64).max(1),
+            capacity: (span / interval) as usize,
+        }
+    }
+
+    pub fn record(&mut self, intersection: &mut Intersection) {
+        if !intersection.tick.is_multiple_of(self.interval) {
+            return;
+        }
+        if self.states.len() == self.capacity {
+            self.states.pop_front();
+        }
+        let records = Records {
+            trips: intersection.trips.len(),
+            pedestrian_trips: intersection.pedestrian_trips.len(),
+            cyclist_trips: intersection.cyclist_trips.len(),
+            series: intersection.series.len(),
+        };
+        let trace = intersection.trace.take();
+        let trips = mem::take(&mut intersection.trips);
+        let pedestrian_trips = mem::take(&mut intersection.pedestrian_trips);
+        let cyclist_trips = mem::take(&mut intersection.cyclist_trips);
+        let series = mem::take(&mut intersection.series);
+        self.states.push_back((intersection.clone(), records));
+        intersection.trace = trace;
+        intersection.trips = trips;
+        intersection.pedestrian_trips = pedestrian_trips;
+        intersection.cyclist_trips = cyclist_trips;
+        intersection.series = series;
+    }
+
+    /// Latest kept state at least `seconds` before `now`, newer states are dropped.
+    pub fn rewind(&mut self, now: &Intersection, seconds: f32) -> Option<Intersection> {
+        let target = now.time() - seconds;
+        while self.states.len() > 1 && self.states.back()?.0.time() > target {
+            self.states.pop_back();
+        }
+        let (state, records) = self.states.back()?;
+        let mut restored = state.clone();
+        restored.trips = now.trips[..records.trips].to_vec();
+        restored.pedestrian_trips = now.pedestrian_trips[..records.pedestrian_trips].to_vec();
+        restored.cyclist_trips = now.cyclist_trips[..records.cyclist_trips].to_vec();
+        restored.series = now.series[..records.series].to_vec();
+        Some(restored)
+    }
+
+    pub fn clear(&mut self) {
+        self.states.clear();
+    }
+}
@@ -0,0 +1,202 @@
+use crate::constants::*;
+use crate::intersection::Intersection;
+use crate::report::los_color;
+use crate::route::Approach;
+use crate::scenario::ControllerKind;
+use crate::statistics::{los_label, LevelOfService, Trip};
+use macroquad::prelude::*;
+use std::collections::VecDeque;
+
+/// Seconds between two samples of the sparklines.
+const SAMPLE_PERIOD: f32 = 1.0;
+/// How much history the sparklines show.
+const HISTORY: f32 = 300.0;
+const PANEL_X: f32 = 10.0;
+const PANEL_Y: f32 = 40.0;
+const PANEL_WIDTH: f32 = 330.0;
+const SPARK_WIDTH: f32 = 140.0;
+const SPARK_HEIGHT: f32 = 22.0;
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    tick: u64,
+    passed: u32,
+    in_system: usize,
+    queued: usize,
+    delay: f32,
+}
+
+/// Live statistics panel toggled with H.
+#[derive(Default)]
+pub struct Hud {
+    pub visible: bool,
+    samples: VecDeque<Sample>,
+}
+
+impl Hud {
+    pub fn update(&mut self, intersection: &Intersection) {
+        let period = (SAMPLE_PERIOD / TICK).round() as u64;
+        // Rewinding or loading a snapshot moves the clock back.
+        self.samples.retain(|s| s.tick <= intersection.tick);
+        if let Some(last) = self.samples.back() {
+            if intersection.tick < last.tick + period {
+                return;
+            }
+        }
+        if self.samples.len() as f32 >= HISTORY / SAMPLE_PERIOD {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample {
+            tick: intersection.tick,
+            passed: intersection.number_of_passed_vehicles,
+            in_system: intersection.vehicules().len(),
+            queued: intersection.queue_lengths().values().sum(),
+            delay: intersection.average_delay(),
+        });
+    }
+
+    /// Vehicles per minute that left over the minute before each sample.
+    fn throughput(&self) -> Vec<f32> {
+        let window = (60.0 / SAMPLE_PERIOD) as usize;
+        (0..self.samples.len())
+            .map(|index| {
+                let from = &self.samples[index.saturating_sub(window)];
+                let to = &self.samples[index];
+                let minutes = (to.tick - from.tick) as f32 * TICK / 60.0;
+                if minutes > 0.0 {
+                    (to.passed - from.passed) as f32 / minutes
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    pub fn draw(&self, intersection: &Intersection) {
+        if !self.visible {
+            return;
+        }
+        let lengths = intersection.queue_lengths();
+        let queues: Vec<String> = lengths
+            .iter()
+            .map(|(approach, length)| format!("{:?} {}", approach, length))
+            .collect();
+        let throughput = self.throughput();
+        let rows: [(String, Vec<f32>); 4] = [
+            (
+                format!(
+                    "Throughput: {:.1} veh/min",
+                    throughput.last().copied().unwrap_or(0.0)
+                ),
+                throughput,
+            ),
+            (
+                format!("In system: {}", intersection.vehicules().len()),
+                self.samples.iter().map(|s| s.in_system as f32).collect(),
+            ),
+            (
+                format!("Queued: {}", lengths.values().sum::<usize>()),
+                self.samples.iter().map(|s| s.queued as f32).collect(),
+            ),
+            (
+                format!("Avg delay: {:.2} s", intersection.average_delay()),
+                self.samples.iter().map(|s| s.delay).collect(),
+            ),
+        ];
+
+        let height = 40.0 + rows.len() as f32 * 30.0 + 74.0;
+        draw_rectangle(
+            PANEL_X,
+            PANEL_Y,
+            PANEL_WIDTH,
+            height,
+            Color::new(1.0, 1.0, 1.0, 0.85),
+        );
+        draw_rectangle_lines(PANEL_X, PANEL_Y, PANEL_WIDTH, height, 1.0, DARKGRAY);
+
+        let mut y = PANEL_Y + 22.0;
+        draw_text(
+            &format!("Passed: {}", intersection.number_of_passed_vehicles),
+            PANEL_X + 8.0,
+            y,
+            20.0,
+            BLACK,
+        );
+        for (label, values) in &rows {
+            y += 30.0;
+            draw_text(label, PANEL_X + 8.0, y, 20.0, BLACK);
+            sparkline(
+                values,
+                PANEL_X + PANEL_WIDTH - SPARK_WIDTH - 8.0,
+                y - SPARK_HEIGHT + 4.0,
+            );
+        }
+        y += 30.0;
+        draw_text(
+            &format!("Queues: {}", queues.join("  ")),
+            PANEL_X + 8.0,
+            y,
+            18.0,
+            BLACK,
+        );
+        y += 24.0;
+        let mut x = PANEL_X + 8.0;
+        draw_text("LOS:", x, y, 18.0, BLACK);
+        x += measure_text("LOS:", None, 18, 1.0).width + 6.0;
+        let signalized = intersection.controller.kind == ControllerKind::Signal;
+        let levels = Approach::ALL
+            .iter()
+            .map(|approach| {
+                let trips: Vec<&Trip> = intersection
+                    .trips
+                    .iter()
+                    .filter(|t| t.route.approach() == *approach)
+                    .collect();
+                (
+                    format!("{:?}", approach),
+                    LevelOfService::of(&trips, signalized),
+                )
+            })
+            .chain(std::iter::once((
+                "All".to_string(),
+                LevelOfService::of(&intersection.trips.iter().collect::<Vec<_>>(), signalized),
+            )));
+        for (name, level) in levels {
+            let text = format!("{} {}", name, los_label(level));
+            let color = level.map_or(BLACK, los_color);
+            draw_text(&text, x, y, 18.0, color);
+            x += measure_text(&text, None, 18, 1.0).width + 10.0;
+        }
+        y += 24.0;
+        draw_text(
+            &format!(
+                "Close calls: {}   Collisions: {}",
+                intersection.close_calls, intersection.collisions
+            ),
+            PANEL_X + 8.0,
+            y,
+            20.0,
+            if intersection.collisions > 0 {
+                RED
+            } else {
+                BLACK
+            },
+        );
+    }
+}
+
+fn sparkline(values: &[f32], x: f32, y: f32) {
+    draw_rectangle_lines(x, y, SPARK_WIDTH, SPARK_HEIGHT, 1.0, LIGHTGRAY);
+    if values.len() < 2 {
+        return;
+    }
+    let max = values.iter().copied().fold(0.0, f32::max).max(1.0);
+    let step = SPARK_WIDTH / (HISTORY / SAMPLE_PERIOD - 1.0);
+    let start = x + SPARK_WIDTH - step * (values.len() - 1) as f32;
+    for (index, pair) in values.windows(2).enumerate() {
+        let x1 = start + step * index as f32;
+        let y1 = y + SPARK_HEIGHT - pair[0] / max * SPARK_HEIGHT;
+        let y2 = y + SPARK_HEIGHT - pair[1] / max * SPARK_HEIGHT;
+        draw_line(x1, y1, x1 + step, y2, 1.5, DARKBLUE);
+    }
+}
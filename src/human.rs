@@ -0,0 +1,156 @@
+use crate::constants::*;
+use crate::rng::Rng;
+use crate::vehicule::Vehicule;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Share and behaviour of the human drivers of a scenario.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HumanDrivers {
+    /// Share of vehicles driving autonomously, the others are driven by humans.
+    pub penetration: f32,
+    /// Seconds a human wants before the next conflicting vehicle arrives.
+    pub critical_gap: f32,
+    /// Seconds between a gap opening and the human moving into it.
+    pub reaction_time: f32,
+    /// Each driver's gap and reaction time are drawn uniformly within this
+    /// share of the values above.
+    pub randomness: f32,
+}
+
+impl Default for HumanDrivers {
+    fn default() -> Self {
+        Self {
+            penetration: 1.0,
+            critical_gap: 4.0,
+            reaction_time: 1.0,
+            randomness: 0.3,
+        }
+    }
+}
+
+impl HumanDrivers {
+    /// Who drives the next vehicle, `None` for the autonomous ones. Fully
+    /// autonomous traffic leaves `rng` untouched, runs stay as they were.
+    pub fn draw(&self, rng: &mut Rng) -> Option<HumanDriver> {
+        if self.penetration >= 1.0 || rng.gen_f32() < self.penetration {
+            return None;
+        }
+        let mut vary = |value: f32| value * (1.0 + self.randomness * (2.0 * rng.gen_f32() - 1.0));
+        Some(HumanDriver {
+            critical_gap: vary(self.critical_gap).max(0.0),
+            reaction_ticks: (vary(self.reaction_time).max(0.0) / TICK).round() as u32,
+            waited: 0,
+        })
+    }
+}
+
+/// A driver who ignores the controller's grants and crosses when the gap
+/// to conflicting traffic looks large enough.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HumanDriver {
+    /// In seconds.
+    pub critical_gap: f32,
+    pub reaction_ticks: u32,
+    /// Ticks the current gap has been seen.
+    pub waited: u32,
+}
+
+impl HumanDriver {
+    /// Whether the driver moves, given whether the way looks clear this tick.
+    pub fn react(&mut self, clear: bool) -> bool {
+        if !clear {
+            self.waited = 0;
+            return false;
+        }
+        self.waited += 1;
+        self.waited > self.reaction_ticks
+    }
+}
+
+/// Vehicles on routes conflicting with `car` that make its driver wait:
+/// the ones crossing and the ones arriving within `critical_gap`. Vehicles
+/// already slowing down for the stop line are trusted to yield.
+pub fn rejected_gaps(car: &Vehicule, critical_gap: f32, cars: &HashMap<u32, Vehicule>) -> Vec<u32> {
+    let conflicts = car.route.not_allowed_to_go();
+    let mut blocking: Vec<u32> = cars
+        .values()
+        .filter(|c| conflicts.contains(&c.route) && !c.after_intersect())
+        .filter(|c| {
+            if !c.avant_intersect() && !c.is_vitesse_max() {
+                return false;
+            }
+            let distance = c.distance_to_stop();
+            if distance <= 0.0 {
+                return true;
+            }
+            c.speed() > 0.0 && distance / c.speed() * TICK < critical_gap
+        })
+        .map(|c| c.id)
+        .collect();
+    blocking.sort();
+    blocking
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::route::Route;
+    use crate::vehicule::VehicleType;
+
+    /// A driver waiting to go north to south and a car just arriving from
+    /// the west, with the seconds it needs to reach its stop line.
+    fn crossing() -> (Vehicule, HashMap<u32, Vehicule>, f32) {
+        let driver = Vehicule::spawn(Route::NS, VehicleType::Car, 1);
+        let other = Vehicule::spawn(Route::WE, VehicleType::Car, 2);
+        assert!(Route::NS.not_allowed_to_go().contains(&Route::WE));
+        let arrival = other.distance_to_stop() / other.speed() * TICK;
+        (driver, HashMap::from([(other.id, other)]), arrival)
+    }
+
+    #[test]
+    fn rejects_a_gap_below_the_critical_gap() {
+        let (driver, cars, arrival) = crossing();
+        assert_eq!(rejected_gaps(&driver, arrival + 1.0, &cars), vec![2]);
+    }
+
+    #[test]
+    fn accepts_a_larger_gap() {
+        let (driver, cars, arrival) = crossing();
+        assert!(rejected_gaps(&driver, arrival - 1.0, &cars).is_empty());
+    }
+
+    #[test]
+    fn moves_after_the_reaction_time() {
+        let mut driver = HumanDriver {
+            critical_gap: 4.0,
+            reaction_ticks: 3,
+            waited: 0,
+        };
+        assert!(!driver.react(true));
+        assert!(!driver.react(true));
+        // A closing gap starts the reaction over.
+        assert!(!driver.react(false));
+        for _ in 0..3 {
+            assert!(!driver.react(true));
+        }
+        assert!(driver.react(true));
+    }
+
+    #[test]
+    fn penetration_sets_the_share_of_autonomous_vehicles() {
+        let mut rng = Rng::new(7);
+        let mut fleet = |penetration| {
+            let drivers = HumanDrivers {
+                penetration,
+                ..Default::default()
+            };
+            (0..200)
+                .filter(|_| drivers.draw(&mut rng).is_none())
+                .count()
+        };
+        assert_eq!(fleet(1.0), 200);
+        assert_eq!(fleet(0.0), 0);
+    }
+}
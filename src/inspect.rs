@@ -0,0 +1,154 @@
+use crate::intersection::Intersection;
+use crate::overlay::{centre, draw_polyline};
+use crate::units::*;
+use crate::vehicule::Vehicule;
+use macroquad::prelude::*;
+
+const PANEL_WIDTH: f32 = 270.0;
+const TRAJECTORY_COLOR: Color = Color::new(0.6, 0.0, 0.8, 0.8);
+
+/// Click on a car to inspect it, F follows it with the camera, right click
+/// or Backspace closes the panel.
+#[derive(Default)]
+pub struct Inspector {
+    pub selected: Option<u32>,
+    pub follow: bool,
+    pub unit: SpeedUnit,
+}
+
+impl Inspector {
+    /// `mouse` is the cursor in world coordinates.
+    pub fn handle_input(&mut self, intersection: &Intersection, mouse: Vec2) {
+        if is_mouse_button_pressed(MouseButton::Left) && !self.over_panel() {
+            if let Some(car) = intersection
+                .vehicules()
+                .into_iter()
+                .find(|c| c.contains(mouse))
+            {
+                self.selected = Some(car.id);
+            }
+        }
+        if is_mouse_button_pressed(MouseButton::Right) || is_key_pressed(KeyCode::Backspace) {
+            self.selected = None;
+        }
+        if is_key_pressed(KeyCode::F) && self.selected.is_some() {
+            self.follow = !self.follow;
+        }
+        if self.car(intersection).is_none() {
+            self.selected = None;
+            self.follow = false;
+        }
+    }
+
+    fn over_panel(&self) -> bool {
+        self.selected.is_some() && mouse_position().0 > screen_width() - PANEL_WIDTH
+    }
+
+    fn car<'a>(&self, intersection: &'a Intersection) -> Option<&'a Vehicule> {
+        let id = self.selected?;
+        intersection.vehicules().into_iter().find(|c| c.id == id)
+    }
+
+    /// Point the camera should centre on while following.
+    pub fn follow_target(&self, intersection: &Intersection) -> Option<Vec2> {
+        if !self.follow {
+            return None;
+        }
+        self.car(intersection).map(|car| centre(car.coordonne))
+    }
+
+    /// Trajectory and highlight, drawn in world coordinates.
+    pub fn draw_world(&self, intersection: &Intersection) {
+        let Some(car) = self.car(intersection) else {
+            return;
+        };
+        draw_polyline(&car.planned_path(), TRAJECTORY_COLOR);
+        let here = centre(car.coordonne);
+        draw_rectangle_lines(
+            here.x - car.rectangle.0 / 2.0 - 3.0,
+            here.y - car.rectangle.1 / 2.0 - 3.0,
+            car.rectangle.0 + 6.0,
+            car.rectangle.1 + 6.0,
+            2.0,
+            TRAJECTORY_COLOR,
+        );
+        for id in intersection.yielding_to(car) {
+            if let Some(other) = intersection.vehicules().into_iter().find(|c| c.id == id) {
+                let there = centre(other.coordonne);
+                draw_line(here.x, here.y, there.x, there.y, 1.5, RED);
+            }
+        }
+    }
+
+    /// Side panel, drawn in screen coordinates.
+    pub fn draw_panel(&self, intersection: &Intersection) {
+        let Some(car) = self.car(intersection) else {
+            return;
+        };
+        let x = screen_width() - PANEL_WIDTH;
+        draw_rectangle(
+            x,
+            0.0,
+            PANEL_WIDTH,
+            screen_height(),
+            Color::new(1.0, 1.0, 1.0, 0.9),
+        );
+        draw_line(x, 0.0, x, screen_height(), 2.0, DARKGRAY);
+
+        let queue_position = match intersection.queue().iter().position(|id| *id == car.id) {
+            Some(position) => format!("{}", position + 1),
+            None => "-".to_string(),
+        };
+        let yielding: Vec<String> = intersection
+            .yielding_to(car)
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        let lines = [
+            format!("Vehicle {}", car.id),
+            format!("Type: {}", car.kind.name()),
+            format!(
+                "Driver: {}",
+                if car.human.is_some() {
+                    "human"
+                } else {
+                    "autonomous"
+                }
+            ),
+            format!("Route: {:?}", car.route),
+            format!("Direction: {:?}", car.direction),
+            format!(
+                "Speed: {}",
+                self.unit.format(metres_per_second(car.speed()))
+            ),
+            format!("Turned: {}", car.turned),
+            format!("State: {:?}", intersection.control_state(car)),
+            format!(
+                "Time in system: {:.1} s",
+                intersection.time_in_system(car.id).unwrap_or(0.0)
+            ),
+            format!("Queue position: {}", queue_position),
+            format!(
+                "Yielding to: {}",
+                if yielding.is_empty() {
+                    "-".to_string()
+                } else {
+                    yielding.join(", ")
+                }
+            ),
+            String::new(),
+            format!(
+                "F: {} camera",
+                if self.follow {
+                    "release"
+                } else {
+                    "follow with"
+                }
+            ),
+            "Right click: close".to_string(),
+        ];
+        for (index, line) in lines.iter().enumerate() {
+            draw_text(line, x + 12.0, 40.0 + index as f32 * 26.0, 22.0, BLACK);
+        }
+    }
+}
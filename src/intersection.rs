@@ -1,80 +1,337 @@
 use crate::constants::*;
+use crate::cyclist::{self, Cyclist};
+use crate::human::{self, HumanDrivers};
+use crate::pedestrian::{self, Pedestrian};
+use crate::rng::Rng;
 use crate::route::*;
-use std::time::{Duration, Instant};
+use crate::scenario::{ControllerConfig, ControllerKind, Layout};
+use crate::statistics::{CyclistTrip, PedestrianTrip, SeriesPoint, Trip, VehicleLog};
+use crate::trace::{Arrival, Trace};
+use crate::units::*;
+use std::time::Duration;
 
 use crate::vehicule::*;
 use macroquad::prelude::*;
-use std::collections::{HashMap, HashSet, VecDeque};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
+/// What the controller is currently doing with a vehicle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlState {
+    Approaching,
+    Waiting,
+    Crossing,
+    Cleared,
+}
+
+impl ControlState {
+    pub const ALL: [ControlState; 4] = [
+        ControlState::Approaching,
+        ControlState::Waiting,
+        ControlState::Crossing,
+        ControlState::Cleared,
+    ];
+}
 
-#[derive(Debug, PartialEq, Clone)]
+/// Why a vehicle may not start across the intersection yet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wait {
+    /// Held for an emergency vehicle close to its stop line.
+    Emergency,
+    /// Its signal is red.
+    Red,
+    /// The external controller has not granted it.
+    NotGranted,
+    /// The smart controller serves this vehicle first.
+    Queue(u32),
+    /// A human driver is not at the stop line yet, or waits behind one who is.
+    Lane,
+    /// A human driver finds the gap in front of this vehicle too short.
+    Gap(u32),
+    /// This vehicle is crossing a conflicting track.
+    Crossing(u32),
+    /// Pedestrians or cyclists have the right of way.
+    GivingWay,
+}
+
+impl Wait {
+    /// The vehicle waited for, if any.
+    pub fn vehicle(&self) -> Option<u32> {
+        match self {
+            Wait::Queue(id) | Wait::Gap(id) | Wait::Crossing(id) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Intersection {
-    tracks: HashMap<Route, Vec<u32>>,
+    tracks: BTreeMap<Route, Vec<u32>>,
     car_id: u32,
-    occupied_tracks: HashMap<Route, HashSet<u32>>,
+    occupied_tracks: BTreeMap<Route, HashSet<u32>>,
     cars: HashMap<u32, Vehicule>,
     queue: VecDeque<u32>,
 
+    pub controller: ControllerConfig,
+    /// Vehicles an external controller allowed to cross.
+    #[serde(default)]
+    pub grants: HashSet<u32>,
+    /// Approach speeds in pixels per tick set by an external controller.
+    #[serde(default)]
+    pub speed_commands: HashMap<u32, f32>,
+    pub layout: Layout,
+    #[serde(default)]
+    pub human_drivers: HumanDrivers,
+    pub rng: Rng,
+    pub tick: u64,
+    /// Every `add_car` call when recording a spawn trace.
+    #[serde(skip)]
+    pub trace: Option<Trace>,
+
     pub number_of_passed_vehicles: u32,
-    pub max_velocity: f32,
-    pub min_velocity: f32,
-    pub collapse: u32,
+    /// Vehicles that asked to enter, per approach.
+    #[serde(default)]
+    pub arrivals: BTreeMap<Approach, u32>,
+    /// Arrivals waiting for room at the start of their lane, with their arrival tick.
+    #[serde(default)]
+    entry_queues: BTreeMap<Route, VecDeque<(VehicleType, u64)>>,
+    /// Pairs of cars whose bodies overlapped.
+    pub collisions: u32,
+    /// Cars on different routes passing within `CLOSE_CALL_MARGIN` inside the intersection.
     pub close_calls: u32,
+    /// Sum over passed vehicles of their time in system minus their free-flow time.
+    pub total_delay: f32,
+    pub trips: Vec<Trip>,
+    /// Pedestrians waiting at a curb or crossing.
+    #[serde(default)]
+    pub pedestrians: Vec<Pedestrian>,
+    #[serde(default)]
+    pedestrian_id: u32,
+    #[serde(default)]
+    pub pedestrian_trips: Vec<PedestrianTrip>,
+    /// Vehicles that passed within `CLOSE_CALL_MARGIN` of a pedestrian
+    /// walking across their crosswalk, or hit one.
+    #[serde(default)]
+    pub pedestrian_close_calls: u32,
+    #[serde(default)]
+    pub pedestrian_collisions: u32,
+    /// Cyclists on the bike lanes, drawn only when the scenario has some.
+    #[serde(default)]
+    pub bike_lanes: bool,
+    #[serde(default)]
+    pub cyclists: Vec<Cyclist>,
+    #[serde(default)]
+    cyclist_id: u32,
+    #[serde(default)]
+    pub cyclist_trips: Vec<CyclistTrip>,
+    /// Vehicles turning right that passed within `CLOSE_CALL_MARGIN` of a
+    /// cyclist on their way over the bike lane, or hit one.
+    #[serde(default)]
+    pub cyclist_close_calls: u32,
+    #[serde(default)]
+    pub cyclist_collisions: u32,
+    pub series: Vec<SeriesPoint>,
     pub max_time: Duration,
     pub min_time: Duration,
-    vehicle_start_times: HashMap<u32, Instant>,
+    logs: HashMap<u32, VehicleLog>,
+    /// Cars that left the map during `drive_cars`, until `remove_cars` records them.
+    #[serde(skip)]
+    exited: HashMap<u32, Vehicule>,
+    /// Free-flow times already measured, by route, vehicle type and speed.
+    #[serde(skip)]
+    free_flow_times: BTreeMap<(Route, VehicleType, u32), f32>,
+    close_pairs: HashSet<(u32, u32)>,
+    colliding_pairs: HashSet<(u32, u32)>,
+    /// Cyclist and vehicle ids.
+    #[serde(default)]
+    cyclist_close_pairs: HashSet<(u32, u32)>,
+    #[serde(default)]
+    pedestrian_close_pairs: HashSet<(u32, u32)>,
+    #[serde(default)]
+    pedestrian_colliding_pairs: HashSet<(u32, u32)>,
+    #[serde(default)]
+    cyclist_colliding_pairs: HashSet<(u32, u32)>,
+}
+
+impl Default for Intersection {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Intersection {
     pub fn new() -> Intersection {
+        Intersection::with_config(ControllerConfig::default(), Layout::default(), 0)
+    }
+
+    pub fn with_config(controller: ControllerConfig, layout: Layout, seed: u64) -> Intersection {
         Intersection {
-            tracks: HashMap::new(),
+            tracks: BTreeMap::new(),
             car_id: 0,
-            occupied_tracks: HashMap::new(),
+            occupied_tracks: BTreeMap::new(),
             cars: HashMap::new(),
             queue: VecDeque::new(),
+            controller,
+            grants: HashSet::new(),
+            speed_commands: HashMap::new(),
+            layout,
+            human_drivers: HumanDrivers::default(),
+            rng: Rng::new(seed),
+            tick: 0,
+            trace: None,
             number_of_passed_vehicles: 0,
-            max_velocity: 0.0,
-            min_velocity: 10.0,
-            collapse: 0,
+            arrivals: BTreeMap::new(),
+            entry_queues: BTreeMap::new(),
+            collisions: 0,
             close_calls: 0,
+            total_delay: 0.0,
+            trips: Vec::new(),
+            pedestrians: Vec::new(),
+            pedestrian_id: 0,
+            pedestrian_trips: Vec::new(),
+            pedestrian_close_calls: 0,
+            pedestrian_collisions: 0,
+            bike_lanes: false,
+            cyclists: Vec::new(),
+            cyclist_id: 0,
+            cyclist_trips: Vec::new(),
+            cyclist_close_calls: 0,
+            cyclist_collisions: 0,
+            series: Vec::new(),
+            exited: HashMap::new(),
+            free_flow_times: BTreeMap::new(),
+            close_pairs: HashSet::new(),
+            colliding_pairs: HashSet::new(),
+            cyclist_close_pairs: HashSet::new(),
+            pedestrian_close_pairs: HashSet::new(),
+            pedestrian_colliding_pairs: HashSet::new(),
+            cyclist_colliding_pairs: HashSet::new(),
 
             max_time: Duration::from_secs(0),
             min_time: Duration::from_secs(u64::MAX),
-            vehicle_start_times: HashMap::new(),
+            logs: HashMap::new(),
         }
     }
 
-    pub fn add_car(&mut self, routes: Vec<Route>, color: Vec<Texture2D>) {
-        let route: Route = generate_route(routes);
-        if !self.can_add(route) {
-            return;
+    /// Simulated seconds since the start of the run.
+    pub fn time(&self) -> f32 {
+        self.tick as f32 * TICK
+    }
+
+    pub fn step(&mut self) {
+        self.release_entry_queues();
+        self.drive_cars();
+        self.walk_pedestrians();
+        self.ride_cyclists();
+        self.remove_cars();
+        if self
+            .tick
+            .is_multiple_of((SERIES_PERIOD / TICK).round() as u64)
+        {
+            self.series.push(SeriesPoint {
+                time: self.time(),
+                passed: self.number_of_passed_vehicles,
+                in_system: self.cars.len(),
+                queues: self.queue_lengths(),
+            });
         }
+        self.tick += 1;
+    }
 
-        self.car_id += 1;
-        self.vehicle_start_times.insert(self.car_id, Instant::now());
+    pub fn add_car(&mut self, routes: Vec<Route>, kind: VehicleType) {
+        let routes: Vec<Route> = routes
+            .into_iter()
+            .filter(|r| self.layout.routes.contains(r))
+            .collect();
+        if routes.is_empty() {
+            return;
+        }
+        let route: Route = routes[self.rng.gen_range(0, routes.len())];
+        let time = self.time();
+        if let Some(trace) = &mut self.trace {
+            trace.push(Arrival {
+                time,
+                routes: vec![route],
+                kind,
+            });
+        }
+        *self.arrivals.entry(route.approach()).or_insert(0) += 1;
+        let waiting = self.entry_queues.get(&route).is_some_and(|w| !w.is_empty());
+        if waiting || !self.can_add(route, kind) {
+            self.entry_queues
+                .entry(route)
+                .or_default()
+                .push_back((kind, self.tick));
+            return;
+        }
+        self.spawn_car(route, kind, self.tick);
+    }
 
-        let mut rectangle: (f32, f32) = (CAR_WIDTH, CAR_HEIGHT);
-        let direction = route.get_direction();
-        if direction == Direction::Up || direction == Direction::Down {
-            rectangle = (CAR_HEIGHT, CAR_WIDTH);
+    /// Lets in the arrivals that were waiting for their lane to clear.
+    fn release_entry_queues(&mut self) {
+        let routes: Vec<Route> = self
+            .entry_queues
+            .iter()
+            .filter(|(_, waiting)| !waiting.is_empty())
+            .map(|(route, _)| *route)
+            .collect();
+        for route in routes {
+            let Some(&(kind, arrival)) = self.entry_queues[&route].front() else {
+                continue;
+            };
+            if self.can_add(route, kind) {
+                self.entry_queues.get_mut(&route).unwrap().pop_front();
+                self.spawn_car(route, kind, arrival);
+            }
         }
-        let car_color = match route {
-            Route::SE | Route::EN | Route::NW | Route::WS => color[0].clone(),
-            Route::NS | Route::SN | Route::WE | Route::EW => color[1].clone(),
-            _ => color[2].clone(),
-        };
+    }
 
-        let car = Vehicule::new(
-            route.get_coordinates(),
-            rectangle,
-            car_color,
-            route.get_speed(),
-            self.car_id,
-            direction,
-            route,
-            false,
-        );
+    /// A pedestrian arriving at one end, picked at random, of the crosswalk
+    /// of `crosswalk`, walking at `speed` pixels per tick.
+    pub fn add_pedestrian(&mut self, crosswalk: Approach, speed: f32) {
+        self.pedestrian_id += 1;
+        let forward = self.rng.gen_f32() < 0.5;
+        let offset = (self.rng.gen_f32() - 0.5) * 20.0;
+        self.pedestrians.push(Pedestrian {
+            id: self.pedestrian_id,
+            crosswalk,
+            forward,
+            offset,
+            walked: 0.0,
+            speed,
+            arrival_tick: self.tick,
+            start_tick: None,
+        });
+    }
+
+    /// A cyclist entering the bike lane of `approach`, riding at up to
+    /// `speed` pixels per tick. It starts behind the edge while the start
+    /// of the lane is taken.
+    pub fn add_cyclist(&mut self, approach: Approach, speed: f32) {
+        self.cyclist_id += 1;
+        let start = self
+            .cyclists
+            .iter()
+            .rfind(|c| c.approach == approach)
+            .map_or(0.0, |last| last.room_behind().min(0.0));
+        self.cyclists.push(Cyclist::new(
+            self.cyclist_id,
+            approach,
+            start,
+            speed,
+            self.tick,
+        ));
+    }
+
+    /// Puts a car at the start of `route`; its time in the system counts from `arrival`.
+    fn spawn_car(&mut self, route: Route, kind: VehicleType, arrival: u64) {
+        self.car_id += 1;
+        self.logs.insert(self.car_id, VehicleLog::new(arrival));
+
+        let mut car = Vehicule::spawn(route, kind, self.car_id);
+        car.vitesse_max = self.layout.vitesse_max();
+        car.human = self.human_drivers.draw(&mut self.rng);
 
         let current_cars_on_track = self.tracks.get_mut(&route);
         let mut cars = match current_cars_on_track {
@@ -86,81 +343,565 @@ impl Intersection {
         self.cars.insert(car.id, car);
     }
 
-    fn can_add(&mut self, route: Route) -> bool {
+    /// Whether a `kind` fits at the start of `route`: the last vehicle on it
+    /// is at least the security distance ahead, plus what the two are longer
+    /// than cars.
+    fn can_add(&mut self, route: Route, kind: VehicleType) -> bool {
         let start_coordinates = route.get_coordinates();
         let cars = self.tracks.get_mut(&route);
-        return match cars {
+        match cars {
             Some(cars) => {
                 let last_car_id = cars.as_slice().last().unwrap();
-                let last_car_position = self.cars.get(last_car_id).unwrap().coordonne;
+                let last_car = self.cars.get(last_car_id).unwrap();
+                let last_car_position = last_car.coordonne;
+                let security_distance = self.controller.security_distance
+                    + (last_car.kind.spec().length + kind.spec().length) / 2.0
+                    - CAR_WIDTH;
                 if (route == Route::NS || route == Route::NW || route == Route::NE)
-                    && last_car_position.y <= start_coordinates.y + SECURITY_DISTANCE
+                    && last_car_position.y <= start_coordinates.y + security_distance
                 {
                     return false;
                 }
                 if (route == Route::SN || route == Route::SE || route == Route::SW)
-                    && last_car_position.y + SECURITY_DISTANCE >= start_coordinates.y
+                    && last_car_position.y + security_distance >= start_coordinates.y
                 {
                     return false;
                 }
                 if (route == Route::WE || route == Route::WS || route == Route::WN)
-                    && last_car_position.x <= start_coordinates.x + SECURITY_DISTANCE
+                    && last_car_position.x <= start_coordinates.x + security_distance
                 {
                     return false;
                 }
                 if (route == Route::EW || route == Route::EN || route == Route::ES)
-                    && last_car_position.x + SECURITY_DISTANCE >= start_coordinates.x
+                    && last_car_position.x + security_distance >= start_coordinates.x
                 {
                     return false;
                 }
                 true
             }
             None => true,
-        };
+        }
+    }
+
+    /// Vehicles currently in the system, ordered by id.
+    pub fn vehicules(&self) -> Vec<&Vehicule> {
+        let mut cars: Vec<&Vehicule> = self.cars.values().collect();
+        cars.sort_by_key(|c| c.id);
+        cars
+    }
+
+    pub fn queue(&self) -> &VecDeque<u32> {
+        &self.queue
+    }
+
+    pub fn occupied_tracks(&self) -> &BTreeMap<Route, HashSet<u32>> {
+        &self.occupied_tracks
+    }
+
+    /// Simulated seconds since the vehicle was spawned.
+    pub fn time_in_system(&self, id: u32) -> Option<f32> {
+        self.logs
+            .get(&id)
+            .map(|log| (self.tick - log.spawn_tick) as f32 * TICK)
+    }
+
+    /// Vehicles currently keeping `car` from crossing, see `waiting_for`.
+    pub fn yielding_to(&self, car: &Vehicule) -> Vec<u32> {
+        let mut blocking: Vec<u32> = self
+            .waiting_for(car)
+            .iter()
+            .filter_map(Wait::vehicle)
+            .collect();
+        blocking.sort();
+        blocking.dedup();
+        blocking
+    }
+
+    /// Everything currently keeping `car` from crossing, empty once it may go.
+    pub fn waiting_for(&self, car: &Vehicule) -> Vec<Wait> {
+        let (priority, held) = self.preemption();
+        let mut giving_way = self.giving_way_to_pedestrians();
+        giving_way.extend(self.giving_way_to_cyclists());
+        self.waits(car, &priority, &held, &giving_way)
+    }
+
+    /// What `drive_cars` checks before letting `car` cross, given the
+    /// emergency preemption and who gives way to people this tick. Human
+    /// drivers also take their reaction time once the way looks clear.
+    fn waits(
+        &self,
+        car: &Vehicule,
+        priority: &HashSet<u32>,
+        held: &HashSet<u32>,
+        giving_way: &HashSet<u32>,
+    ) -> Vec<Wait> {
+        let conflicts = car.route.not_allowed_to_go();
+        let mut waits = Vec::new();
+        let decides = !conflicts.is_empty() && !priority.contains(&car.id);
+        if decides && held.contains(&car.id) {
+            waits.push(Wait::Emergency);
+        } else if decides {
+            let green = self.controller.kind != ControllerKind::Signal
+                || self.controller.is_green(car.route.approach(), self.time());
+            if let Some(driver) = &car.human {
+                // Humans only know the signal and what they see, and decide
+                // once nothing waits ahead of them in their lane.
+                if !green {
+                    waits.push(Wait::Red);
+                }
+                let lane = self.tracks.get(&car.route).map_or(&[][..], |ids| ids);
+                let ahead = lane
+                    .iter()
+                    .position(|id| *id == car.id)
+                    .filter(|ind| *ind >= 1)
+                    .and_then(|ind| self.cars.get(&lane[ind - 1]));
+                if car.avant_intersect() || ahead.is_some_and(|c| !c.is_vitesse_max()) {
+                    waits.push(Wait::Lane);
+                }
+                waits.extend(
+                    human::rejected_gaps(car, driver.critical_gap, &self.cars)
+                        .into_iter()
+                        .map(Wait::Gap),
+                );
+            } else {
+                match self.controller.kind {
+                    // Humans cannot be scheduled, the controller serves the
+                    // first autonomous vehicle.
+                    ControllerKind::Smart => {
+                        if let Some(head) = self.queue.iter().find(|id| {
+                            !held.contains(id)
+                                && !giving_way.contains(id)
+                                && self.cars.get(id).is_none_or(|c| c.human.is_none())
+                        }) {
+                            if *head != car.id {
+                                waits.push(Wait::Queue(*head));
+                            }
+                        }
+                    }
+                    ControllerKind::Signal if !green => waits.push(Wait::Red),
+                    ControllerKind::Signal => {}
+                    ControllerKind::External if !self.grants.contains(&car.id) => {
+                        waits.push(Wait::NotGranted)
+                    }
+                    ControllerKind::External => {}
+                }
+            }
+        }
+        for route in conflicts {
+            if let Some(ids) = self.occupied_tracks.get(&route) {
+                let mut fast: Vec<u32> = ids
+                    .iter()
+                    .filter(|id| self.cars.get(id).is_some_and(|c| c.is_vitesse_max()))
+                    .copied()
+                    .collect();
+                fast.sort();
+                waits.extend(fast.into_iter().map(Wait::Crossing));
+            }
+        }
+        if giving_way.contains(&car.id) {
+            waits.push(Wait::GivingWay);
+        }
+        waits
+    }
+
+    pub fn control_state(&self, car: &Vehicule) -> ControlState {
+        if self.queue.contains(&car.id) {
+            ControlState::Waiting
+        } else if car.after_intersect() {
+            ControlState::Cleared
+        } else if car.is_vitesse_max() {
+            ControlState::Crossing
+        } else {
+            ControlState::Approaching
+        }
+    }
+
+    pub fn draw_pedestrians(&self) {
+        for person in &self.pedestrians {
+            person.draw();
+        }
+    }
+
+    /// Drawn under the vehicles, when the scenario has cyclists.
+    pub fn draw_bike_lanes(&self) {
+        if self.bike_lanes {
+            cyclist::draw_bike_lanes();
+        }
+    }
+
+    pub fn draw_cyclists(&self) {
+        for rider in &self.cyclists {
+            rider.draw();
+        }
+    }
+
+    /// Vehicles that have to give way before turning right over a bike
+    /// lane: the ones not granted yet while a cyclist goes on over their
+    /// exit, or waits there since before the vehicle reached its zone.
+    pub fn giving_way_to_cyclists(&self) -> HashSet<u32> {
+        let mut giving_way = HashSet::new();
+        for approach in Approach::ALL {
+            let riders: Vec<&Cyclist> = self
+                .cyclists
+                .iter()
+                .filter(|c| c.approach == approach && !c.has_passed())
+                .collect();
+            if riders.is_empty() {
+                continue;
+            }
+            for id in self
+                .tracks
+                .get(&cyclist::right_turn(approach))
+                .into_iter()
+                .flatten()
+            {
+                if self.cars.get(id).is_none_or(|car| car.is_vitesse_max()) {
+                    continue;
+                }
+                let entry = self
+                    .logs
+                    .get(id)
+                    .and_then(|log| log.entry_tick)
+                    .unwrap_or(self.tick);
+                if riders
+                    .iter()
+                    .any(|c| c.committed || c.zone_tick.is_some_and(|zone| zone < entry))
+                {
+                    giving_way.insert(*id);
+                }
+            }
+        }
+        giving_way
+    }
+
+    /// Lets cyclists in their zone go on once no vehicle turning right over
+    /// their lane is about to or was there first, and rides them all, each
+    /// behind the one ahead. The signal lets them go with their approach.
+    fn ride_cyclists(&mut self) {
+        let time = self.time();
+        for index in 0..self.cyclists.len() {
+            let rider = &self.cyclists[index];
+            if rider.committed || !rider.in_zone() {
+                continue;
+            }
+            let zone_tick = rider.zone_tick.unwrap_or(self.tick);
+            let area = cyclist::conflict_area(rider.approach);
+            let green = self.controller.kind != ControllerKind::Signal
+                || self.controller.is_green(rider.approach, time);
+            let clear = self
+                .tracks
+                .get(&cyclist::right_turn(rider.approach))
+                .into_iter()
+                .flatten()
+                .filter_map(|id| self.cars.get(id))
+                .all(|car| {
+                    if car.is_vitesse_max() {
+                        return !car.before(area);
+                    }
+                    car.avant_intersect()
+                        || self
+                            .logs
+                            .get(&car.id)
+                            .and_then(|log| log.entry_tick)
+                            .is_none_or(|entry| entry >= zone_tick)
+                });
+            let rider = &mut self.cyclists[index];
+            rider.zone_tick = Some(zone_tick);
+            rider.committed = green && clear;
+        }
+        let mut limits: BTreeMap<Approach, f32> = BTreeMap::new();
+        for rider in &mut self.cyclists {
+            let mut limit = limits
+                .get(&rider.approach)
+                .copied()
+                .unwrap_or(f32::INFINITY);
+            if !rider.committed {
+                limit = limit.min(rider.stop_position());
+            }
+            rider.ride(limit);
+            limits.insert(rider.approach, rider.room_behind());
+        }
+        self.check_cyclist_conflicts();
+        let tick = self.tick;
+        let (out, riding) = self
+            .cyclists
+            .drain(..)
+            .partition::<Vec<Cyclist>, _>(|c| c.is_out_of_road());
+        self.cyclists = riding;
+        self.cyclist_trips.extend(out.into_iter().map(|rider| {
+            let free_flow_time = rider.free_flow_time();
+            let travel_time = (tick - rider.spawn_tick) as f32 * TICK;
+            CyclistTrip {
+                id: rider.id,
+                approach: rider.approach,
+                spawn_time: rider.spawn_tick as f32 * TICK,
+                exit_time: tick as f32 * TICK,
+                free_flow_time,
+                delay: travel_time - free_flow_time,
+                stopped_time: rider.stopped_ticks as f32 * TICK,
+            }
+        }));
+    }
+
+    /// Counts each pair of a cyclist and a vehicle turning right over its
+    /// lane once when they start colliding, or passing within
+    /// `CLOSE_CALL_MARGIN` while the vehicle is on the conflict area.
+    fn check_cyclist_conflicts(&mut self) {
+        let mut close = HashSet::new();
+        let mut colliding = HashSet::new();
+        for rider in &self.cyclists {
+            let body = rider.body();
+            let area = cyclist::conflict_area(rider.approach);
+            let near = Rect::new(
+                body.x - CLOSE_CALL_MARGIN,
+                body.y - CLOSE_CALL_MARGIN,
+                body.w + 2.0 * CLOSE_CALL_MARGIN,
+                body.h + 2.0 * CLOSE_CALL_MARGIN,
+            );
+            for id in self
+                .tracks
+                .get(&cyclist::right_turn(rider.approach))
+                .into_iter()
+                .flatten()
+            {
+                let Some(car) = self.cars.get(id) else {
+                    continue;
+                };
+                let pair = (rider.id, car.id);
+                if car.body().overlaps(&body) {
+                    colliding.insert(pair);
+                } else if car.body().overlaps(&area) && car.body().overlaps(&near) {
+                    close.insert(pair);
+                }
+            }
+        }
+        self.cyclist_close_calls += close.difference(&self.cyclist_close_pairs).count() as u32;
+        self.cyclist_collisions +=
+            colliding.difference(&self.cyclist_colliding_pairs).count() as u32;
+        self.cyclist_close_pairs = close;
+        self.cyclist_colliding_pairs = colliding;
+    }
+
+    /// Counts each pair of a walking pedestrian and a vehicle once when they
+    /// start colliding, or passing within `CLOSE_CALL_MARGIN` while the
+    /// vehicle is on the crosswalk.
+    fn check_pedestrian_conflicts(&mut self) {
+        let mut close = HashSet::new();
+        let mut colliding = HashSet::new();
+        for person in self.pedestrians.iter().filter(|p| p.is_walking()) {
+            let body = person.body();
+            let area = pedestrian::crosswalk(person.crosswalk);
+            let near = Rect::new(
+                body.x - CLOSE_CALL_MARGIN,
+                body.y - CLOSE_CALL_MARGIN,
+                body.w + 2.0 * CLOSE_CALL_MARGIN,
+                body.h + 2.0 * CLOSE_CALL_MARGIN,
+            );
+            for car in self.cars.values() {
+                let pair = (person.id, car.id);
+                if car.body().overlaps(&body) {
+                    colliding.insert(pair);
+                } else if car.body().overlaps(&area) && car.body().overlaps(&near) {
+                    close.insert(pair);
+                }
+            }
+        }
+        self.pedestrian_close_calls +=
+            close.difference(&self.pedestrian_close_pairs).count() as u32;
+        self.pedestrian_collisions += colliding
+            .difference(&self.pedestrian_colliding_pairs)
+            .count() as u32;
+        self.pedestrian_close_pairs = close;
+        self.pedestrian_colliding_pairs = colliding;
+    }
+
+    /// Vehicles that have to give way before turning over a crosswalk: the
+    /// ones not granted yet while people cross it, or wait there since
+    /// before the vehicle reached its zone.
+    pub fn giving_way_to_pedestrians(&self) -> HashSet<u32> {
+        let mut giving_way = HashSet::new();
+        for approach in Approach::ALL {
+            let people: Vec<&Pedestrian> = self
+                .pedestrians
+                .iter()
+                .filter(|p| p.crosswalk == approach)
+                .collect();
+            if people.is_empty() {
+                continue;
+            }
+            for route in pedestrian::conflicting_routes(approach) {
+                for id in self.tracks.get(&route).into_iter().flatten() {
+                    if self.cars.get(id).is_none_or(|car| car.is_vitesse_max()) {
+                        continue;
+                    }
+                    let entry = self
+                        .logs
+                        .get(id)
+                        .and_then(|log| log.entry_tick)
+                        .unwrap_or(self.tick);
+                    if people
+                        .iter()
+                        .any(|p| p.is_walking() || p.arrival_tick < entry)
+                    {
+                        giving_way.insert(*id);
+                    }
+                }
+            }
+        }
+        giving_way
+    }
+
+    /// Lets waiting pedestrians step off the curb once no turning vehicle
+    /// is about to drive over their crosswalk or was there first, and walks
+    /// the others, each stopping rather than stepping up to a vehicle. The
+    /// signal only lets them go with the traffic alongside.
+    fn walk_pedestrians(&mut self) {
+        let time = self.time();
+        for index in 0..self.pedestrians.len() {
+            let person = &self.pedestrians[index];
+            if person.is_walking() {
+                continue;
+            }
+            let green = self.controller.kind != ControllerKind::Signal
+                || self
+                    .controller
+                    .is_green(pedestrian::parallel_approach(person.crosswalk), time);
+            let clear = pedestrian::conflicting_routes(person.crosswalk)
+                .iter()
+                .filter_map(|route| self.tracks.get(route))
+                .flatten()
+                .filter_map(|id| self.cars.get(id))
+                .all(|car| {
+                    if car.is_vitesse_max() {
+                        return !car.before(pedestrian::crosswalk(person.crosswalk));
+                    }
+                    car.avant_intersect()
+                        || self
+                            .logs
+                            .get(&car.id)
+                            .and_then(|log| log.entry_tick)
+                            .is_none_or(|entry| entry >= person.arrival_tick)
+                });
+            if green && clear {
+                self.pedestrians[index].start_tick = Some(self.tick);
+            }
+        }
+        let bodies: Vec<Rect> = self.cars.values().map(|car| car.body()).collect();
+        for person in &mut self.pedestrians {
+            if !person.is_walking() {
+                continue;
+            }
+            let body = person.body();
+            person.walked += person.speed;
+            let next = person.body();
+            if bodies
+                .iter()
+                .any(|car| next.overlaps(car) && !body.overlaps(car))
+            {
+                person.walked -= person.speed;
+            }
+        }
+        self.check_pedestrian_conflicts();
+        let tick = self.tick;
+        let (crossed, waiting) = self
+            .pedestrians
+            .drain(..)
+            .partition::<Vec<Pedestrian>, _>(|p| p.has_crossed());
+        self.pedestrians = waiting;
+        self.pedestrian_trips
+            .extend(crossed.into_iter().map(|person| PedestrianTrip {
+                id: person.id,
+                crosswalk: person.crosswalk,
+                arrival_time: person.arrival_tick as f32 * TICK,
+                start_time: person.start_tick.unwrap_or(tick) as f32 * TICK,
+                exit_time: tick as f32 * TICK,
+            }));
     }
 
-    pub fn draw_cars(&self, car1: Texture2D, car2: Texture2D, car3: Texture2D) {
+    pub fn draw_cars(&self, textures: &Textures) {
         for (_route, cars) in self.tracks.iter() {
             cars.iter().for_each(|id| {
                 let car = self.cars.get(id).unwrap();
-                car.draw(car1.clone(), car2.clone(), car3.clone());
+                car.draw(textures);
             })
         }
     }
 
+    /// Vehicles given way to while an emergency vehicle has not crossed:
+    /// the emergency vehicles and the ones ahead of them in their lane get
+    /// priority, vehicles on conflicting routes are held once the emergency
+    /// vehicle is within `PREEMPTION_HORIZON` of its stop line.
+    pub fn preemption(&self) -> (HashSet<u32>, HashSet<u32>) {
+        let emergencies: Vec<&Vehicule> = self
+            .cars
+            .values()
+            .filter(|car| car.kind == VehicleType::Emergency && !car.after_intersect())
+            .collect();
+        let mut priority = HashSet::new();
+        let mut held = HashSet::new();
+        for car in self.cars.values() {
+            if emergencies
+                .iter()
+                .any(|e| e.route == car.route && e.id >= car.id)
+            {
+                priority.insert(car.id);
+            } else if emergencies.iter().any(|e| {
+                let near = !e.avant_intersect()
+                    || e.distance_to_stop() <= e.speed() * PREEMPTION_HORIZON / TICK;
+                near && (e.route.not_allowed_to_go().contains(&car.route)
+                    || car.route.not_allowed_to_go().contains(&e.route))
+            }) {
+                held.insert(car.id);
+            }
+        }
+        (priority, held)
+    }
+
     pub fn drive_cars(&mut self) {
+        let (priority, held) = self.preemption();
+        let mut giving_way = self.giving_way_to_pedestrians();
+        giving_way.extend(self.giving_way_to_cyclists());
+        let crosswalks_in_use: HashSet<Approach> =
+            self.pedestrians.iter().map(|p| p.crosswalk).collect();
+        let walking: Vec<Rect> = self
+            .pedestrians
+            .iter()
+            .filter(|p| p.is_walking())
+            .map(|p| p.body())
+            .collect();
         for (route, cars_ids) in self.tracks.iter() {
             for (ind, car_id) in cars_ids.iter().enumerate() {
                 let cars = self.cars.clone();
                 let cars_on_cross_road = self.occupied_tracks.get(route);
 
-                let car: &mut Vehicule = self.cars.get_mut(car_id).unwrap();
+                let waits = self.waits(&cars[car_id], &priority, &held, &giving_way);
 
-                let mut can_go = route.not_allowed_to_go().len() == 0
-                    || self.queue.is_empty()
-                    || self.queue[0] == car.id;
+                let car: &mut Vehicule = self.cars.get_mut(car_id).unwrap();
 
-                route.not_allowed_to_go().iter().for_each(|r| {
-                    let not_speed_up = match self.occupied_tracks.get(r) {
-                        Some(a) => {
-                            let mut res = true;
-                            a.iter().for_each(|f| {
-                                res = res && !cars.get(f).unwrap().is_vitesse_max();
-                            });
-                            res
-                        }
-                        None => true,
-                    };
-                    can_go = can_go && (self.occupied_tracks.get(r).is_none() || not_speed_up);
-                });
+                let mut can_go = waits.is_empty();
+                let decides = !route.not_allowed_to_go().is_empty()
+                    && !priority.contains(&car.id)
+                    && !held.contains(&car.id);
+                if let (Some(driver), true) = (&mut car.human, decides) {
+                    let clear = !waits
+                        .iter()
+                        .any(|w| matches!(w, Wait::Red | Wait::Lane | Wait::Gap(_)));
+                    can_go = driver.react(clear) && can_go;
+                }
+                if !can_go && held.contains(&car.id) && car.on_cross_road() && !car.is_vitesse_max()
+                {
+                    if let Some(log) = self.logs.get_mut(car_id) {
+                        log.held_ticks += 1;
+                    }
+                }
 
-                if !cars_on_cross_road.is_none() {
-                    let mut all_cars = cars_on_cross_road.unwrap().clone();
+                if let Some(cars_on_cross_road) = cars_on_cross_road {
+                    let mut all_cars = cars_on_cross_road.clone();
                     if !car.avant_intersect() && !car.is_vitesse_max() {
                         if can_go {
                             car.speed_up();
-                            if !self.queue.is_empty() && self.queue[0] == car.id {
-                                self.queue.pop_front();
+                            if let Some(position) = self.queue.iter().position(|id| *id == car.id) {
+                                self.queue.remove(position);
                             }
                         } else {
                             car.slow_down();
@@ -181,8 +922,8 @@ impl Intersection {
                     let cars = HashSet::from_iter(vec![car.id]);
                     if can_go {
                         car.speed_up();
-                        if !self.queue.is_empty() && self.queue[0] == car.id {
-                            self.queue.pop_front();
+                        if let Some(position) = self.queue.iter().position(|id| *id == car.id) {
+                            self.queue.remove(position);
                         }
                     } else {
                         car.slow_down();
@@ -193,87 +934,211 @@ impl Intersection {
                     self.occupied_tracks.insert(*route, cars);
                 }
                 if car.avant_intersect() && ind >= 1 {
-                    if !cars.get(&cars_ids[ind - 1]).is_none()
-                        && cars.get(&cars_ids[ind - 1]).unwrap().is_vitesse_min()
+                    if cars
+                        .get(&cars_ids[ind - 1])
+                        .is_some_and(|prev| prev.is_vitesse_min())
                     {
                         car.slow_down();
                     } else {
                         car.vitesse = route.get_speed();
                     }
                 }
+                if car.avant_intersect() && car.human.is_none() {
+                    if let Some(vitesse) = self.speed_commands.get(car_id) {
+                        car.set_speed(*vitesse);
+                    }
+                }
+                let mut distance = 0.0;
+                // A car is never held back by the ones following it in its lane,
+                // the snap of a turn could otherwise lock both of them. It keeps
+                // a few pixels to the one ahead for that snap, and the room its own
+                // turn takes back. It stops before people walking across its way
+                // and, until it is granted, before the crosswalk of its arm while
+                // people wait there or cross it.
+                let body = car.body();
+                let crosswalk = pedestrian::crosswalk(route.approach());
+                let before_crosswalk = !car.is_vitesse_max()
+                    && crosswalks_in_use.contains(&route.approach())
+                    && !body.overlaps(&crosswalk);
+                let free = |moved: &Vehicule| {
+                    let held_by_people = before_crosswalk && moved.body().overlaps(&crosswalk)
+                        || walking
+                            .iter()
+                            .any(|person| pedestrian::closing_in(body, moved.body(), *person));
+                    !held_by_people
+                        && !cars.values().any(|c| {
+                            let margin = if c.route == *route {
+                                5.0 + c.turn_setback()
+                            } else {
+                                1.0
+                            };
+                            c.id != moved.id
+                                && !cars_ids[ind + 1..].contains(&c.id)
+                                && moved.overlaps(c, margin)
+                        })
+                };
                 let mut car_clone = car.clone();
                 car_clone.move_car();
-                if (car_clone.is_vitesse_max() || !car_clone.in_stop_zone())
-                    && !cars.values().any(|c| {
-                        c.id != car_clone.id
-                            && intersect(
-                                car_clone.coordonne,
-                                c.coordonne,
-                                vec2(
-                                    car_clone.coordonne.x + car_clone.rectangle.0 + 5.0,
-                                    car_clone.coordonne.y + car_clone.rectangle.1 + 5.0,
-                                ),
-                                vec2(
-                                    c.coordonne.x + c.rectangle.0 + 5.0,
-                                    c.coordonne.y + c.rectangle.1 + 5.0,
-                                ),
-                            )
-                    })
-                {
-                    car.move_car();
+                let mut limit = None;
+                if car_clone.is_vitesse_max() || !car_clone.in_stop_zone() {
+                    if free(&car_clone) {
+                        limit = Some(f32::INFINITY);
+                    } else {
+                        // Held by a vehicle: drive up to it at the speed the gap allows
+                        // rather than stopping dead.
+                        let (mut low, mut high) = (0.0, car_clone.speed());
+                        for _ in 0..GAP_SEARCH_STEPS {
+                            let middle = (low + high) / 2.0;
+                            let mut candidate = car.clone();
+                            candidate.move_car_within(middle);
+                            if free(&candidate) {
+                                low = middle;
+                            } else {
+                                high = middle;
+                            }
+                        }
+                        if low > 0.0 {
+                            limit = Some(low);
+                        }
+                    }
+                }
+                if let Some(limit) = limit {
+                    car.move_car_within(limit);
+                    distance = car.speed();
+                    let entered = !car.avant_intersect();
                     if car.is_out_of_road() {
-                        self.cars.remove(car_id);
+                        if let Some(car) = self.cars.remove(car_id) {
+                            self.exited.insert(car.id, car);
+                        }
                         self.number_of_passed_vehicles += 1;
                     }
+                    if let Some(log) = self.logs.get_mut(car_id) {
+                        if entered && log.entry_tick.is_none() {
+                            log.entry_tick = Some(self.tick);
+                        }
+                    }
+                } else {
+                    car.allure = 0.0;
+                }
+                if let Some(log) = self.logs.get_mut(car_id) {
+                    log.moved(distance);
                 }
-            }
-            for car in self.cars.values() {
-                let speed = (car.vitesse.0.powi(2) + car.vitesse.1.powi(2)).sqrt();
-                self.max_velocity = self.max_velocity.max(speed);
-                self.min_velocity = self.min_velocity.min(speed);
             }
         }
         self.check_close_calls();
-        
-    }
-    
-    fn check_security_distance(&self, car: &Vehicule, prev_car: &Vehicule) -> bool {
-        let distance = match car.direction {
-            Direction::Up | Direction::Down => (car.coordonne.y - prev_car.coordonne.y).abs(),
-            Direction::Left | Direction::Right => (car.coordonne.x - prev_car.coordonne.x).abs(),
-        };
-        
-        distance >= SECURITY_DISTANCE
     }
-    
+
+    /// Counts each pair of cars once when they start colliding or passing too
+    /// close, and keeps the smallest gap each car had to another.
     fn check_close_calls(&mut self) {
-        for car in self.cars.values() {
-            for other_car in self.cars.values() {
-                if car.id != other_car.id && !self.check_security_distance(car, other_car) {
-                    // self.close_calls += 1;
-                    self.close_calls = 0;
-                    break;
+        let cars = self.vehicules();
+        let mut close = HashSet::new();
+        let mut colliding = HashSet::new();
+        let mut gaps = Vec::new();
+        for (index, car) in cars.iter().enumerate() {
+            for other in &cars[index + 1..] {
+                let pair = (car.id, other.id);
+                gaps.push((pair, car.gap(other)));
+                if car.overlaps(other, 0.0) {
+                    colliding.insert(pair);
+                } else if car.route != other.route
+                    && (car.on_cross_road() || other.on_cross_road())
+                    && car.overlaps(other, CLOSE_CALL_MARGIN)
+                {
+                    close.insert(pair);
                 }
             }
         }
+        for ((a, b), gap) in gaps {
+            for id in [a, b] {
+                if let Some(log) = self.logs.get_mut(&id) {
+                    log.gap(gap);
+                }
+            }
+        }
+        self.close_calls += close.difference(&self.close_pairs).count() as u32;
+        self.collisions += colliding.difference(&self.colliding_pairs).count() as u32;
+        self.close_pairs = close;
+        self.colliding_pairs = colliding;
     }
 
-    pub fn remove_cars(&mut self) {
-        let now = Instant::now();
-        let mut to_remove = Vec::new();
+    /// Average over passed vehicles of the delay in seconds.
+    pub fn average_delay(&self) -> f32 {
+        if self.number_of_passed_vehicles == 0 {
+            return 0.0;
+        }
+        self.total_delay / self.number_of_passed_vehicles as f32
+    }
+
+    /// Vehicles still before the intersection that are queued or slowed, or
+    /// waiting to enter, per approach.
+    pub fn queue_lengths(&self) -> BTreeMap<Approach, usize> {
+        let mut lengths: BTreeMap<Approach, usize> =
+            Approach::ALL.iter().map(|a| (*a, 0)).collect();
+        for car in self.cars.values() {
+            if car.after_intersect() {
+                continue;
+            }
+            if self.queue.contains(&car.id) || car.is_vitesse_min() {
+                *lengths.entry(car.route.approach()).or_insert(0) += 1;
+            }
+        }
+        for (route, waiting) in &self.entry_queues {
+            *lengths.entry(route.approach()).or_insert(0) += waiting.len();
+        }
+        lengths
+    }
 
+    /// Arrivals that have not entered their lane yet.
+    pub fn waiting_to_enter(&self) -> usize {
+        self.entry_queues.values().map(|w| w.len()).sum()
+    }
+
+    pub fn remove_cars(&mut self) {
         for (route, cars) in self.tracks.clone().iter() {
             let mut left_cars: Vec<u32> = vec![];
             for &car_id in cars {
                 if self.cars.contains_key(&car_id) {
                     left_cars.push(car_id);
                 } else {
-                    if let Some(start_time) = self.vehicle_start_times.get(&car_id) {
-                        let duration = now.duration_since(*start_time);
+                    self.grants.remove(&car_id);
+                    self.speed_commands.remove(&car_id);
+                    if let Some(log) = self.logs.remove(&car_id) {
+                        let duration =
+                            Duration::from_secs_f32((self.tick - log.spawn_tick) as f32 * TICK);
                         self.max_time = self.max_time.max(duration);
                         self.min_time = self.min_time.min(duration);
+                        let kind = self
+                            .exited
+                            .get(&car_id)
+                            .map_or(VehicleType::Car, |c| c.kind);
+                        let vitesse_max = self.layout.vitesse_max();
+                        let free_flow = *self
+                            .free_flow_times
+                            .entry((*route, kind, vitesse_max.to_bits()))
+                            .or_insert_with(|| free_flow_time(*route, kind, vitesse_max));
+                        let delay = duration.as_secs_f32() - free_flow;
+                        self.total_delay += delay;
+                        if let Some(car) = self.exited.remove(&car_id) {
+                            self.trips.push(Trip {
+                                id: car_id,
+                                route: *route,
+                                kind: car.kind,
+                                spawn_time: log.spawn_tick as f32 * TICK,
+                                entry_time: log.entry_tick.unwrap_or(self.tick) as f32 * TICK,
+                                exit_time: self.time(),
+                                free_flow_time: free_flow,
+                                delay,
+                                min_gap: log.min_gap.map(metres),
+                                stops: log.stops,
+                                stopped_time: log.stopped_ticks as f32 * TICK,
+                                average_speed: metres(log.distance) / duration.as_secs_f32(),
+                                max_speed: metres_per_second(log.max_speed),
+                                held_time: log.held_ticks as f32 * TICK,
+                                human: car.human.is_some(),
+                            });
+                        }
                     }
-                    to_remove.push(car_id);
                 }
             }
             if !left_cars.is_empty() {
@@ -282,25 +1147,174 @@ impl Intersection {
                 self.tracks.remove(route);
             }
         }
+    }
+}
 
-        for car_id in to_remove {
-            self.vehicle_start_times.remove(&car_id);
+/// Free-flow travel time: seconds a lone vehicle of type `kind` needs to
+/// cover the length of `route` at its desired speed, the route speed on the
+/// approach and `vitesse_max` from the intersection on. Measured by driving
+/// it through an empty intersection.
+pub fn free_flow_time(route: Route, kind: VehicleType, vitesse_max: f32) -> f32 {
+    let mut car = Vehicule::spawn(route, kind, 0);
+    car.vitesse_max = vitesse_max;
+    let mut ticks = 0;
+    loop {
+        if car.on_cross_road() && !car.is_vitesse_max() {
+            car.speed_up();
         }
+        car.move_car();
+        if car.is_out_of_road() {
+            break;
+        }
+        ticks += 1;
     }
+    ticks as f32 * TICK
 }
 
-fn generate_route(routes: Vec<Route>) -> Route {
-    let n: usize = rand::gen_range(0, routes.len());
-    return routes[n];
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scenario::Layout;
 
-fn intersect(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
-    return ((((a.x >= b.x && a.x <= d.x) || (c.x >= b.x && c.x <= d.x))
-        && ((a.y >= b.y && a.y <= d.y) || (c.y >= b.y && c.y <= d.y)))
-        || (((b.x >= a.x && b.x <= c.x) || (d.x >= a.x && d.x <= c.x))
-            && ((b.y >= a.y && b.y <= c.y) || (d.y >= a.y && d.y <= c.y))))
-        || ((((a.x >= b.x && a.x <= d.x) || (c.x >= b.x && c.x <= d.x))
-            && ((b.y >= a.y && b.y <= c.y) || (d.y >= a.y && d.y <= c.y)))
-            || (((b.x >= a.x && b.x <= c.x) || (d.x >= a.x && d.x <= c.x))
-                && ((a.y >= b.y && a.y <= d.y) || (c.y >= b.y && c.y <= d.y))));
+    fn run(kind: ControllerKind, route: Route, seconds: f32) -> Intersection {
+        let controller = ControllerConfig {
+            kind,
+            ..Default::default()
+        };
+        let mut intersection = Intersection::with_config(controller, Layout::default(), 0);
+        intersection.add_car(vec![route], VehicleType::Car);
+        while intersection.time() < seconds {
+            intersection.step();
+        }
+        intersection
+    }
+
+    #[test]
+    fn waits_at_a_red_signal() {
+        // West and east are red for the first phase.
+        let intersection = run(ControllerKind::Signal, Route::WE, 10.0);
+        let car = intersection.vehicules()[0];
+        assert!(!car.in_stop_zone());
+        assert_eq!(intersection.waiting_for(car), vec![Wait::Red]);
+        assert!(intersection.yielding_to(car).is_empty());
+    }
+
+    #[test]
+    fn waits_for_a_grant() {
+        let intersection = run(ControllerKind::External, Route::NS, 10.0);
+        let car = intersection.vehicules()[0];
+        assert!(!car.in_stop_zone());
+        assert_eq!(intersection.waiting_for(car), vec![Wait::NotGranted]);
+    }
+
+    #[test]
+    fn yields_to_the_vehicle_crossing() {
+        let mut intersection = run(ControllerKind::Smart, Route::NS, 0.0);
+        while intersection
+            .vehicules()
+            .first()
+            .is_some_and(|car| !car.is_vitesse_max())
+        {
+            intersection.step();
+        }
+        let crossing = intersection.vehicules()[0].id;
+        let mut other = Vehicule::spawn(Route::WE, VehicleType::Car, 99);
+        other.vitesse_max = intersection.layout.vitesse_max();
+        assert_eq!(intersection.yielding_to(&other), vec![crossing]);
+        assert!(intersection
+            .waiting_for(&other)
+            .contains(&Wait::Crossing(crossing)));
+    }
+
+    #[test]
+    fn a_right_turn_gives_way_to_a_committed_cyclist() {
+        let mut intersection = run(ControllerKind::Smart, Route::NW, 0.0);
+        intersection.bike_lanes = true;
+        // Slow enough for the car to reach the bike lane first.
+        intersection.add_cyclist(Approach::North, pixels_per_tick(5.0));
+        intersection.cyclists[0].committed = true;
+        let car = intersection.vehicules()[0].id;
+        let mut gave_way = false;
+        while intersection.time() < 80.0 {
+            intersection.step();
+            if let Some(turning) = intersection.vehicules().into_iter().find(|c| c.id == car) {
+                gave_way |= intersection.waiting_for(turning).contains(&Wait::GivingWay);
+            }
+        }
+        assert!(gave_way);
+        assert_eq!(intersection.cyclist_trips.len(), 1);
+        assert_eq!(intersection.number_of_passed_vehicles, 1);
+        assert_eq!(intersection.cyclist_close_calls, 0);
+        assert_eq!(intersection.cyclist_collisions, 0);
+    }
+
+    #[test]
+    fn a_cyclist_waits_for_a_right_turn_that_was_there_first() {
+        let mut intersection = run(ControllerKind::Smart, Route::NW, 0.0);
+        intersection.bike_lanes = true;
+        while intersection.vehicules()[0].avant_intersect() {
+            intersection.step();
+        }
+        intersection.add_cyclist(Approach::North, pixels_per_tick(15.0));
+        let rider = &mut intersection.cyclists[0];
+        rider.travelled = rider.stop_position() - 10.0;
+        intersection.step();
+        assert!(!intersection.cyclists[0].committed);
+        while intersection.time() < 30.0 {
+            intersection.step();
+        }
+        assert_eq!(intersection.number_of_passed_vehicles, 1);
+        assert_eq!(intersection.cyclist_trips.len(), 1);
+        assert!(intersection.cyclist_trips[0].stopped_time > 0.0);
+        assert_eq!(intersection.cyclist_close_calls, 0);
+        assert_eq!(intersection.cyclist_collisions, 0);
+    }
+
+    #[test]
+    fn a_turn_gives_way_to_people_on_its_exit_crosswalk() {
+        let mut intersection = run(ControllerKind::Smart, Route::WN, 0.0);
+        intersection.add_pedestrian(Approach::North, 0.05);
+        intersection.pedestrians[0].start_tick = Some(0);
+        let mut gave_way = false;
+        while intersection.time() < 10.0 {
+            intersection.step();
+            let car = intersection.vehicules()[0];
+            gave_way |= intersection.waiting_for(car).contains(&Wait::GivingWay);
+            assert!(!car.is_vitesse_max());
+        }
+        assert!(gave_way);
+        intersection.pedestrians[0].speed = pixels_per_tick(1.3 * 3.6);
+        while intersection.time() < 40.0 {
+            intersection.step();
+        }
+        assert_eq!(intersection.number_of_passed_vehicles, 1);
+        assert_eq!(intersection.pedestrian_close_calls, 0);
+        assert_eq!(intersection.pedestrian_collisions, 0);
+    }
+
+    #[test]
+    fn stops_before_an_occupied_crosswalk() {
+        let controller = ControllerConfig::default();
+        let mut intersection = Intersection::with_config(controller, Layout::default(), 0);
+        // Someone slowly walking over the lane from the north.
+        intersection.add_pedestrian(Approach::North, 0.05);
+        let person = &mut intersection.pedestrians[0];
+        person.forward = true;
+        person.walked = 60.0;
+        person.start_tick = Some(0);
+        intersection.add_car(vec![Route::NS], VehicleType::Car);
+        while intersection.time() < 10.0 {
+            intersection.step();
+        }
+        let car = intersection.vehicules()[0];
+        assert_eq!(car.speed(), 0.0);
+        assert!(!car.body().overlaps(&intersection.pedestrians[0].body()));
+        intersection.pedestrians[0].speed = pixels_per_tick(1.3 * 3.6);
+        while intersection.time() < 40.0 {
+            intersection.step();
+        }
+        assert_eq!(intersection.pedestrian_trips.len(), 1);
+        assert_eq!(intersection.number_of_passed_vehicles, 1);
+        assert_eq!(intersection.pedestrian_collisions, 0);
+    }
 }
@@ -0,0 +1,32 @@
+//! Simulation of a smart intersection, shared by the viewer and the batch runner.
+
+pub mod camera;
+pub mod capacity;
+pub mod cli;
+pub mod clock;
+pub mod constants;
+pub mod cyclist;
+pub mod export;
+pub mod external;
+pub mod gym;
+pub mod history;
+pub mod hud;
+pub mod human;
+pub mod inspect;
+pub mod intersection;
+pub mod overlay;
+pub mod pedestrian;
+pub mod playback;
+pub mod recording;
+pub mod report;
+pub mod rng;
+pub mod route;
+pub mod scenario;
+pub mod snapshot;
+pub mod statistics;
+pub mod sweep;
+#[cfg(test)]
+mod test_util;
+pub mod trace;
+pub mod units;
+pub mod vehicule;
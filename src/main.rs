@@ -1,205 +1,474 @@
-use crate::intersection::*;
-use crate::route::*;
+use smart_road::camera::ViewCamera;
+use smart_road::cli::arg_value;
+use smart_road::clock::SimClock;
+use smart_road::constants::TICK;
+use smart_road::external::Connection;
+use smart_road::history::History;
+use smart_road::hud::Hud;
+use smart_road::inspect::Inspector;
+use smart_road::intersection::*;
+use smart_road::recording::Recorder;
+use smart_road::report::Report;
+use smart_road::route::*;
+use smart_road::scenario::{ControllerKind, Scenario};
+use smart_road::statistics::{Statistics, Summary};
+use smart_road::trace::Trace;
+use smart_road::units::SpeedUnit;
+use smart_road::vehicule::{Textures, VehicleType};
+use smart_road::{export, overlay, playback, recording, snapshot};
 use std::time::{Duration, Instant};
 
 use macroquad::prelude::*;
 
-mod constants;
-mod intersection;
-mod route;
-mod vehicule;
+pub enum GameState {
+    Game,
+    Statistics,
+}
 
-struct Window {
-    width: i32,
-    height: i32,
-    title: String,
+/*
+Pour gere la taille de la fenaitre
+*/
+fn window_conf() -> Conf {
+    Conf {
+        window_title: "IBG Road".to_owned(),
+        window_width: 1000,
+        window_height: 1000,
+        window_resizable: true,
+        ..Default::default()
+    }
 }
 
-impl Window {
-    fn new(width: i32, height: i32, title: &str) -> Self {
-        Self {
-            width,
-            height,
-            title: title.to_string(),
+/// Seconds between two states kept for rewinding.
+const REWIND_INTERVAL: f32 = 0.25;
+/// How far back the rewind buffer reaches.
+const REWIND_SPAN: f32 = 30.0;
+/// Seconds jumped back on each press of B.
+const REWIND_STEP: f32 = 3.0;
+
+/// Where S on the report saves the run when no `--baseline` is given.
+const DEFAULT_BASELINE: &str = "baseline.json";
+
+/// `--scenario <file>` loads an experiment instead of an empty keyboard driven run.
+fn scenario_from_args() -> Option<Scenario> {
+    let path = arg_value("--scenario")?;
+    match Scenario::load(&path) {
+        Ok(scenario) => Some(scenario),
+        Err(err) => {
+            eprintln!("cannot load scenario {}: {}", path, err);
+            std::process::exit(1);
         }
     }
+}
 
-    fn set_size(&mut self, width: i32, height: i32) {
-        self.width = width;
-        self.height = height;
+/// Restores a snapshot, a scenario given alongside only swaps the controller
+/// so several controllers can branch from the same state.
+fn load_snapshot(path: &str, scenario: &Option<Scenario>) -> Option<Intersection> {
+    match snapshot::load(path) {
+        Ok(mut intersection) => {
+            if let Some(scenario) = scenario {
+                intersection.controller = scenario.controller.clone();
+            }
+            Some(intersection)
+        }
+        Err(err) => {
+            eprintln!("cannot load snapshot {}: {}", path, err);
+            None
+        }
     }
+}
 
-    fn set_title(&mut self, title: &str) {
-        self.title = title.to_string();
+/// `--replay-trace <file>` replaces the scenario demand by a recorded arrival sequence.
+fn replay_from_args() -> Option<Trace> {
+    let path = arg_value("--replay-trace")?;
+    match Trace::load(&path) {
+        Ok(trace) => Some(trace),
+        Err(err) => {
+            eprintln!("cannot load trace {}: {}", path, err);
+            std::process::exit(1);
+        }
     }
 }
 
-pub fn draw_stats_text(text: &str, y_offset: f32) {
-    let dims = measure_text(text, None, 30, 1.0);
-    draw_text_ex(
-        text,
-        screen_width() / 2.0 - dims.width / 2.0,
-        y_offset,
-        TextParams {
-            font_size: 30,
-            color: BLACK,
-            ..Default::default()
-        },
-    );
+/// `--speed-unit kmh|ms` picks how speeds are displayed, km/h by default.
+fn speed_unit_from_args() -> SpeedUnit {
+    let Some(name) = arg_value("--speed-unit") else {
+        return SpeedUnit::default();
+    };
+    SpeedUnit::from_name(&name).unwrap_or_else(|| {
+        eprintln!("--speed-unit expects kmh or ms, got {}", name);
+        std::process::exit(1);
+    })
 }
 
-pub struct Statistics {
-    passed_intersection: u32,
-    max_velocity: f32,
-    min_velocity: f32,
-    max_time: Duration,
-    min_time: Duration,
-    close_calls: u32,
+/// `--export-every <seconds>` rewrites the export during the run.
+fn export_period_from_args() -> Option<u64> {
+    let value = arg_value("--export-every")?;
+    match value.parse::<f32>() {
+        Ok(seconds) if seconds > 0.0 => Some(((seconds / TICK).round() as u64).max(1)),
+        _ => {
+            eprintln!(
+                "--export-every expects a positive number of seconds, got {}",
+                value
+            );
+            std::process::exit(1);
+        }
+    }
 }
 
-impl Statistics {
-    pub fn new() -> Self {
-        Self {
-            passed_intersection: 0,
-            max_velocity: 0.0,
-            min_velocity: f32::MAX,
-            max_time: Duration::from_secs(0),
-            min_time: Duration::from_secs(u64::MAX),
-            close_calls: 0,
+/// `--external <address>` waits for a controller process before opening the window.
+fn external_from_args() -> Option<Connection> {
+    let address = arg_value("--external")?;
+    eprintln!("waiting for an external controller on {}", address);
+    match Connection::listen(&address) {
+        Ok(connection) => Some(connection),
+        Err(err) => {
+            eprintln!("cannot listen on {}: {}", address, err);
+            std::process::exit(1);
         }
     }
+}
 
-    pub fn update(&mut self, intersection: &Intersection) {
-        self.passed_intersection = intersection.number_of_passed_vehicles;
-        self.max_velocity = intersection.max_velocity;
-        self.min_velocity = intersection.min_velocity;
-        self.close_calls = intersection.close_calls;
-        self.max_time = intersection.max_time;
-        self.min_time = intersection.min_time;
+fn export_statistics(statistics: &Statistics, dir: &str) {
+    if let Err(err) = export::write(statistics, dir) {
+        eprintln!("cannot export statistics to {}: {}", dir, err);
     }
 }
 
-pub enum GameState {
-    Game,
-    Statistics,
+/// Runs one simulation tick: arrivals, the external controller's answer,
+/// physics, then the optional recording.
+fn advance(
+    intersection: &mut Intersection,
+    scenario: &Option<Scenario>,
+    replay: &mut Option<Trace>,
+    recorder: &mut Option<Recorder>,
+    external: &mut Option<Connection>,
+) {
+    if let Some(replay) = replay {
+        replay.spawn(intersection);
+    } else if let Some(scenario) = scenario {
+        scenario.spawn(intersection);
+    }
+    if let Some(connection) = external {
+        if intersection.controller.kind == ControllerKind::External {
+            if let Err(err) = connection.exchange(intersection) {
+                eprintln!("external controller lost ({}), falling back to smart", err);
+                intersection.controller.kind = ControllerKind::Smart;
+                intersection.speed_commands.clear();
+                *external = None;
+            }
+        }
+    }
+    intersection.step();
+    let captured = recorder.as_mut().map(|r| r.capture(intersection));
+    if let Some(Err(err)) = captured {
+        eprintln!("recording stopped: {}", err);
+        *recorder = None;
+    }
 }
 
-/*
-Pour gere la taille de la fenaitre
-*/
-fn window_conf() -> Conf {
-    Conf {
-        window_title: "IBG Road".to_owned(),
-        window_width: 1000,
-        window_height: 1000,
-        window_resizable: false,
-        ..Default::default()
+/// Keeps the recording in step with a restored run.
+fn rewind_recording(recorder: &mut Option<Recorder>, tick: u64) {
+    if let Some(Err(err)) = recorder.as_mut().map(|r| r.rewind(tick)) {
+        eprintln!("recording stopped: {}", err);
+        *recorder = None;
     }
 }
 
 #[macroquad::main(window_conf)]
 
 async fn main() {
-    let mut window = Window::new(500, 500, "IBG Road");
+    let scenario = scenario_from_args();
+    let mut replay = replay_from_args();
+    let record_trace = arg_value("--record-trace");
+    let export_dir = arg_value("--export");
+    let export_period = export_period_from_args();
     let img: Texture2D = load_texture("./assets/road.png").await.unwrap();
-    let car_1: Texture2D = load_texture("./assets/car_blue.png").await.unwrap();
-    let car_2: Texture2D = load_texture("./assets/car_green.png").await.unwrap();
-    let car_3: Texture2D = load_texture("./assets/car_white.png").await.unwrap();
+    let textures = Textures::load().await;
+
+    if let Some(path) = arg_value("--playback") {
+        match recording::load(&path) {
+            Ok(frames) => playback::run(frames, img, textures).await,
+            Err(err) => {
+                eprintln!("cannot load recording {}: {}", path, err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    let mut recorder = arg_value("--record").map(|path| match Recorder::create(&path) {
+        Ok(recorder) => recorder,
+        Err(err) => {
+            eprintln!("cannot create recording {}: {}", path, err);
+            std::process::exit(1);
+        }
+    });
     let mut game_state = GameState::Game;
     let mut statistics = Statistics::new();
-    let mut intersection = Intersection::new();
+    let baseline_path = arg_value("--baseline");
+    let baseline = baseline_path
+        .as_deref()
+        .and_then(|path| match Summary::load(path) {
+            Ok(summary) => Some(summary),
+            Err(err) => {
+                eprintln!("no baseline loaded from {}: {}", path, err);
+                None
+            }
+        });
+    let unit = speed_unit_from_args();
+    let mut report = Report::new(baseline, unit);
+    let mut intersection = match &scenario {
+        Some(scenario) => scenario.build(),
+        None => Intersection::new(),
+    };
+    let mut last_snapshot = arg_value("--load-snapshot");
+    if let Some(path) = &last_snapshot {
+        let Some(restored) = load_snapshot(path, &scenario) else {
+            std::process::exit(1);
+        };
+        if let Some(replay) = &mut replay {
+            replay.seek(restored.time());
+        }
+        intersection = restored;
+    }
+    if record_trace.is_some() {
+        intersection.trace = Some(Trace::new());
+    }
+    let mut external = external_from_args();
+    if external.is_some() {
+        intersection.controller.kind = ControllerKind::External;
+    } else if intersection.controller.kind == ControllerKind::External {
+        eprintln!("the external controller needs --external <address>");
+        std::process::exit(1);
+    }
 
-    let cars: Vec<Texture2D> = vec![car_1.clone(), car_2.clone(), car_3.clone()];
+    let mut clock = SimClock::new();
+    let mut show_overlay = false;
+    let mut inspector = Inspector {
+        unit,
+        ..Default::default()
+    };
+    let mut view = ViewCamera::new();
+    let mut hud = Hud::default();
+    let mut history = History::new(REWIND_INTERVAL, REWIND_SPAN);
     let throttle_duration = Duration::from_millis(200); // 200 milliseconds throttle
     let mut last_key_press = Instant::now() - throttle_duration; // Ensure first key press isn't throttled
 
     loop {
         match game_state {
             GameState::Game => {
+                clock.handle_keys();
+                clock.begin_frame();
+                while clock.tick() {
+                    advance(
+                        &mut intersection,
+                        &scenario,
+                        &mut replay,
+                        &mut recorder,
+                        &mut external,
+                    );
+                    history.record(&mut intersection);
+                    if let (Some(dir), Some(period)) = (&export_dir, export_period) {
+                        if intersection.tick.is_multiple_of(period) {
+                            let mut partial = Statistics::new();
+                            partial.update(&intersection);
+                            export_statistics(&partial, dir);
+                        }
+                    }
+                    if scenario
+                        .as_ref()
+                        .is_some_and(|s| s.is_finished(&intersection))
+                    {
+                        break;
+                    }
+                }
+                let finished = scenario
+                    .as_ref()
+                    .is_some_and(|s| s.is_finished(&intersection));
+                if is_key_pressed(KeyCode::Escape) || finished {
+                    statistics.update(&intersection);
+                    if let Some(dir) = &export_dir {
+                        export_statistics(&statistics, dir);
+                    }
+                    if let Some(recorder) = &mut recorder {
+                        if let Err(err) = recorder.finish() {
+                            eprintln!("cannot write recording: {}", err);
+                        }
+                    }
+                    if let (Some(path), Some(trace)) = (&record_trace, &intersection.trace) {
+                        if let Err(err) = trace.save(path) {
+                            eprintln!("cannot write trace {}: {}", path, err);
+                        }
+                    }
+                    game_state = GameState::Statistics;
+                    last_key_press = Instant::now();
+                }
+
+                view.handle_input();
+                if let Some(target) = inspector.follow_target(&intersection) {
+                    view.target = target;
+                }
+                if !view.is_dragging() {
+                    let mouse = view.screen_to_world(Vec2::from(mouse_position()));
+                    inspector.handle_input(&intersection, mouse);
+                }
+                if is_key_pressed(KeyCode::D) {
+                    show_overlay = !show_overlay;
+                }
+                if is_key_pressed(KeyCode::H) {
+                    hud.visible = !hud.visible;
+                }
+                hud.update(&intersection);
+
                 clear_background(WHITE);
+                set_camera(&view.camera());
                 draw_texture(&img, 0.0, 0.0, WHITE);
+                intersection.draw_bike_lanes();
+                intersection.draw_cars(&textures);
+                intersection.draw_pedestrians();
+                intersection.draw_cyclists();
+                if show_overlay {
+                    overlay::draw(&intersection);
+                }
+                inspector.draw_world(&intersection);
+                set_default_camera();
 
-                intersection.drive_cars();
-                intersection.remove_cars();
-                intersection.draw_cars(car_1.clone(), car_2.clone(), car_3.clone());
+                if show_overlay {
+                    overlay::draw_status(&intersection);
+                }
+                inspector.draw_panel(&intersection);
+                hud.draw(&intersection);
+                let signal = match intersection.controller.kind {
+                    ControllerKind::Signal => {
+                        let green: Vec<String> = Approach::ALL
+                            .iter()
+                            .filter(|a| intersection.controller.is_green(**a, intersection.time()))
+                            .map(|a| format!("{:?}", a))
+                            .collect();
+                        if green.is_empty() {
+                            "  (all red)".to_string()
+                        } else {
+                            format!("  (green: {})", green.join(", "))
+                        }
+                    }
+                    ControllerKind::Smart | ControllerKind::External => String::new(),
+                };
+                draw_text(
+                    &format!(
+                        "{}  t = {:.1} s  controller: {:?}{}",
+                        clock.label(),
+                        intersection.time(),
+                        intersection.controller.kind,
+                        signal
+                    ),
+                    10.0,
+                    25.0,
+                    24.0,
+                    BLACK,
+                );
 
                 if last_key_press.elapsed() >= throttle_duration {
                     if is_key_pressed(KeyCode::Left) {
-                        let routes = vec![Route::EW, Route::EN, Route::ES];
-                        intersection.add_car(routes.clone(), cars.clone());
+                        intersection.add_car(Approach::East.routes(), VehicleType::Car);
                         last_key_press = Instant::now();
                     }
 
                     if is_key_pressed(KeyCode::Right) {
-                        let routes = vec![Route::WE, Route::WS, Route::WN];
-                        intersection.add_car(routes.clone(), cars.clone());
+                        intersection.add_car(Approach::West.routes(), VehicleType::Car);
                         last_key_press = Instant::now();
                     }
 
                     if is_key_pressed(KeyCode::Up) {
-                        let routes = vec![Route::SN, Route::SE, Route::SW];
-                        intersection.add_car(routes.clone(), cars.clone());
+                        intersection.add_car(Approach::South.routes(), VehicleType::Car);
                         last_key_press = Instant::now();
                     }
 
                     if is_key_pressed(KeyCode::Down) {
-                        let routes = vec![Route::NS, Route::NW, Route::NE];
-                        intersection.add_car(routes.clone(), cars.clone());
+                        intersection.add_car(Approach::North.routes(), VehicleType::Car);
                         last_key_press = Instant::now();
                     }
 
                     if is_key_pressed(KeyCode::R) {
-                        let routes = vec![
-                            Route::EW,
-                            Route::WE,
-                            Route::SN,
-                            Route::NS,
-                            Route::EN,
-                            Route::WS,
-                            Route::NW,
-                            Route::SE,
-                            Route::NE,
-                            Route::SW,
-                            Route::WN,
-                            Route::ES,
-                        ];
-                        intersection.add_car(routes.clone(), cars.clone());
+                        intersection.add_car(Route::ALL.to_vec(), VehicleType::Car);
+                        last_key_press = Instant::now();
+                    }
+
+                    if is_key_pressed(KeyCode::E) {
+                        intersection.add_car(Route::ALL.to_vec(), VehicleType::Emergency);
+                        last_key_press = Instant::now();
+                    }
+
+                    if is_key_pressed(KeyCode::S) {
+                        let path = format!("snapshot_{}.json", intersection.tick);
+                        match snapshot::save(&intersection, &path) {
+                            Ok(()) => {
+                                eprintln!("snapshot saved to {}", path);
+                                last_snapshot = Some(path);
+                            }
+                            Err(err) => eprintln!("cannot save snapshot {}: {}", path, err),
+                        }
+                        last_key_press = Instant::now();
+                    }
+
+                    if is_key_pressed(KeyCode::L) {
+                        if let Some(restored) = last_snapshot
+                            .as_ref()
+                            .and_then(|path| load_snapshot(path, &scenario))
+                        {
+                            let mut trace = intersection.trace.take();
+                            if let Some(trace) = &mut trace {
+                                trace.truncate(restored.time());
+                            }
+                            if let Some(replay) = &mut replay {
+                                replay.seek(restored.time());
+                            }
+                            rewind_recording(&mut recorder, restored.tick);
+                            intersection = restored;
+                            intersection.trace = trace;
+                            history.clear();
+                        }
                         last_key_press = Instant::now();
                     }
 
-                    if is_key_pressed(KeyCode::Escape) {
-                        statistics.update(&intersection);
-                        game_state = GameState::Statistics;
-                        window.set_size(500, 400);
-                        window.set_title("Simulation Statistics");
+                    if is_key_pressed(KeyCode::B) {
+                        if let Some(restored) = history.rewind(&intersection, REWIND_STEP) {
+                            let mut trace = intersection.trace.take();
+                            if let Some(trace) = &mut trace {
+                                trace.truncate(restored.time());
+                            }
+                            if let Some(replay) = &mut replay {
+                                replay.seek(restored.time());
+                            }
+                            rewind_recording(&mut recorder, restored.tick);
+                            intersection = restored;
+                            intersection.trace = trace;
+                            clock.paused = true;
+                        }
+                        last_key_press = Instant::now();
+                    }
+
+                    if is_key_pressed(KeyCode::C) {
+                        intersection.controller.kind =
+                            intersection.controller.kind.next(external.is_some());
+                        if intersection.controller.kind != ControllerKind::External {
+                            intersection.speed_commands.clear();
+                        }
+                        eprintln!("controller: {}", intersection.controller.kind.name());
                         last_key_press = Instant::now();
                     }
                 }
             }
             GameState::Statistics => {
-                clear_background(WHITE);
-                draw_stats_text(
-                    &format!("Vehicles passed: {}", statistics.passed_intersection),
-                    50.0,
-                );
-                draw_stats_text(
-                    &format!("Max velocity: {:.1} ", statistics.max_velocity),
-                    100.0,
-                );
-                draw_stats_text(
-                    &format!("Min velocity: {:.1} ", statistics.min_velocity),
-                    150.0,
-                );
-                draw_stats_text(
-                    &format!("Max time: {:.2} s", statistics.max_time.as_secs_f32()),
-                    200.0,
-                );
-                draw_stats_text(
-                    &format!("Min time: {:.2} s", statistics.min_time.as_secs_f32()),
-                    250.0,
-                );
-                draw_stats_text(&format!("Close calls: {}", statistics.close_calls), 300.0);
-                draw_stats_text("Press SPACE to quit", 350.0);
+                report.handle_keys();
+                report.draw(&statistics);
+
+                if is_key_pressed(KeyCode::S) {
+                    let path = baseline_path.as_deref().unwrap_or(DEFAULT_BASELINE);
+                    report.message = match statistics.summary().save(path) {
+                        Ok(()) => format!("Saved as baseline to {}", path),
+                        Err(err) => format!("Cannot save baseline {}: {}", path, err),
+                    };
+                }
 
                 if is_key_pressed(KeyCode::Space) {
                     std::process::exit(0);
@@ -209,4 +478,4 @@ async fn main() {
 
         next_frame().await;
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,190 @@
+use crate::constants::*;
+use crate::cyclist;
+use crate::intersection::Intersection;
+use crate::pedestrian;
+use crate::route::*;
+use crate::vehicule::*;
+use macroquad::prelude::*;
+
+const ZONE_COLOR: Color = Color::new(0.0, 0.3, 1.0, 0.8);
+const STOP_COLOR: Color = Color::new(1.0, 0.0, 0.0, 0.8);
+const APRES_COLOR: Color = Color::new(0.0, 0.7, 0.0, 0.8);
+const PATH_COLOR: Color = Color::new(0.2, 0.2, 0.2, 0.35);
+const OCCUPIED_COLOR: Color = Color::new(1.0, 0.5, 0.0, 0.9);
+
+/// Debug drawing of the zones, crosswalks, bike lane conflict areas, paths, conflict points, occupied
+/// tracks, queue and per-car id and speed. Toggled with D.
+pub fn draw(intersection: &Intersection) {
+    for approach in Approach::ALL {
+        draw_zones(approach);
+        let zone = pedestrian::crosswalk(approach);
+        let color = if intersection
+            .pedestrians
+            .iter()
+            .any(|p| p.crosswalk == approach && p.is_walking())
+        {
+            OCCUPIED_COLOR
+        } else {
+            PATH_COLOR
+        };
+        draw_rectangle_lines(zone.x, zone.y, zone.w, zone.h, 2.0, color);
+        if intersection.bike_lanes {
+            let area = cyclist::conflict_area(approach);
+            let color = if intersection
+                .cyclists
+                .iter()
+                .any(|c| c.approach == approach && c.committed && !c.has_passed())
+            {
+                OCCUPIED_COLOR
+            } else {
+                STOP_COLOR
+            };
+            draw_rectangle_lines(area.x, area.y, area.w, area.h, 2.0, color);
+        }
+    }
+
+    for route in Route::ALL {
+        let color = if intersection.occupied_tracks().contains_key(&route) {
+            OCCUPIED_COLOR
+        } else {
+            PATH_COLOR
+        };
+        draw_polyline(&route.path(), color);
+        if let Some(point) = route.turn_point() {
+            let start = route.get_coordinates();
+            let corner = match route.approach() {
+                Approach::North | Approach::South => vec2(start.x, point),
+                Approach::West | Approach::East => vec2(point, start.y),
+            };
+            let centre = centre(corner);
+            draw_poly_lines(centre.x, centre.y, 4, 6.0, 45.0, 2.0, DARKPURPLE);
+        }
+    }
+
+    for route in Route::ALL {
+        for other in route.not_allowed_to_go() {
+            if other < route {
+                continue;
+            }
+            for point in conflict_points(route, other) {
+                draw_circle(point.x, point.y, 4.0, STOP_COLOR);
+            }
+        }
+    }
+
+    let queue = intersection.queue();
+    for car in intersection.vehicules() {
+        let centre = centre(car.coordonne);
+        let mut label = format!("{} {:.1}", car.id, car.speed());
+        if let Some(position) = queue.iter().position(|id| *id == car.id) {
+            label.push_str(&format!(" q{}", position + 1));
+        }
+        draw_text(&label, centre.x - 20.0, centre.y - 16.0, 18.0, BLACK);
+    }
+}
+
+/// Queue and occupied tracks listing, drawn in screen coordinates.
+pub fn draw_status(intersection: &Intersection) {
+    let queue = intersection.queue();
+    let mut y = screen_height() - 60.0;
+    let queue: Vec<String> = queue.iter().map(|id| id.to_string()).collect();
+    draw_text(
+        &format!("Queue: {}", queue.join(" > ")),
+        10.0,
+        y,
+        20.0,
+        BLACK,
+    );
+    for (route, cars) in intersection.occupied_tracks() {
+        y += 20.0;
+        let mut ids: Vec<&u32> = cars.iter().collect();
+        ids.sort();
+        draw_text(
+            &format!("{:?} occupied by {:?}", route, ids),
+            10.0,
+            y,
+            20.0,
+            OCCUPIED_COLOR,
+        );
+        if y > screen_height() {
+            break;
+        }
+    }
+}
+
+/// Threshold lines of `zone_limits` drawn across the lanes of an approach.
+fn draw_zones(approach: Approach) {
+    let lanes: Vec<Vec2> = approach
+        .routes()
+        .iter()
+        .map(|r| r.get_coordinates())
+        .collect();
+    let direction = approach.routes()[0].get_direction();
+    let limits = zone_limits(direction, CAR_WIDTH);
+    for (value, color) in [
+        (limits.avant, ZONE_COLOR),
+        (limits.stop, STOP_COLOR),
+        (limits.apres, APRES_COLOR),
+    ] {
+        match direction {
+            Direction::Right | Direction::Left => {
+                let top = lanes.iter().map(|l| l.y).fold(f32::MAX, f32::min);
+                let bottom = lanes.iter().map(|l| l.y).fold(f32::MIN, f32::max) + CAR_HEIGHT;
+                draw_line(value, top, value, bottom, 2.0, color);
+            }
+            Direction::Down | Direction::Up => {
+                let left = lanes.iter().map(|l| l.x).fold(f32::MAX, f32::min);
+                let right = lanes.iter().map(|l| l.x).fold(f32::MIN, f32::max) + CAR_HEIGHT;
+                draw_line(left, value, right, value, 2.0, color);
+            }
+        }
+    }
+}
+
+/// Joins the centres of cars placed at `points`.
+pub fn draw_polyline(points: &[Vec2], color: Color) {
+    for pair in points.windows(2) {
+        let (a, b) = (centre(pair[0]), centre(pair[1]));
+        draw_line(a.x, a.y, b.x, b.y, 3.0, color);
+    }
+}
+
+/// Cars are drawn rotated around the centre of a `CAR_WIDTH` x `CAR_HEIGHT` box.
+pub fn centre(coordonne: Vec2) -> Vec2 {
+    coordonne + vec2(CAR_WIDTH / 2.0, CAR_HEIGHT / 2.0)
+}
+
+/// Where the paths of two routes cross.
+fn conflict_points(a: Route, b: Route) -> Vec<Vec2> {
+    let mut points = Vec::new();
+    for first in a.path().windows(2) {
+        for second in b.path().windows(2) {
+            let found = segment_intersection(
+                centre(first[0]),
+                centre(first[1]),
+                centre(second[0]),
+                centre(second[1]),
+            );
+            if let Some(point) = found {
+                points.push(point);
+            }
+        }
+    }
+    points
+}
+
+fn segment_intersection(p1: Vec2, p2: Vec2, q1: Vec2, q2: Vec2) -> Option<Vec2> {
+    let r = p2 - p1;
+    let s = q2 - q1;
+    let denominator = r.perp_dot(s);
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+    let t = (q1 - p1).perp_dot(s) / denominator;
+    let u = (q1 - p1).perp_dot(r) / denominator;
+    if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+        Some(p1 + r * t)
+    } else {
+        None
+    }
+}
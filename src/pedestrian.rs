@@ -0,0 +1,126 @@
+use crate::constants::*;
+use crate::route::*;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const PEDESTRIAN_COLOR: Color = Color::new(0.95, 0.6, 0.1, 1.0);
+/// Pixels between the curb where pedestrians wait and the crosswalk.
+const CURB: f32 = 8.0;
+const RADIUS: f32 = 5.0;
+/// Pixels vehicles keep to people walking, so that they do not pass as
+/// close calls.
+const YIELD_MARGIN: f32 = CLOSE_CALL_MARGIN + 2.0;
+
+/// The crosswalk across the arm of `approach`, as painted on the map.
+pub fn crosswalk(approach: Approach) -> Rect {
+    match approach {
+        Approach::North => Rect::new(360.0, 325.0, 280.0, 30.0),
+        Approach::South => Rect::new(360.0, 645.0, 280.0, 30.0),
+        Approach::West => Rect::new(325.0, 360.0, 30.0, 280.0),
+        Approach::East => Rect::new(648.0, 360.0, 30.0, 280.0),
+    }
+}
+
+/// The turning routes that leave the intersection over the crosswalk of `approach`.
+pub fn conflicting_routes(approach: Approach) -> [Route; 2] {
+    match approach {
+        Approach::North => [Route::WN, Route::EN],
+        Approach::South => [Route::WS, Route::ES],
+        Approach::West => [Route::NW, Route::SW],
+        Approach::East => [Route::NE, Route::SE],
+    }
+}
+
+/// Crosswalk `route` leaves the intersection over, if it turns.
+pub fn crossed_by(route: Route) -> Option<Approach> {
+    Approach::ALL
+        .into_iter()
+        .find(|approach| conflicting_routes(*approach).contains(&route))
+}
+
+/// Distance between two bodies, zero when they overlap.
+fn distance(a: Rect, b: Rect) -> f32 {
+    let x = (b.x - a.right()).max(a.x - b.right()).max(0.0);
+    let y = (b.y - a.bottom()).max(a.y - b.bottom()).max(0.0);
+    vec2(x, y).length()
+}
+
+/// Whether a vehicle moving from `from` to `to` gets closer than
+/// `YIELD_MARGIN` to a pedestrian. Moving away, or along at the same
+/// distance, is fine.
+pub fn closing_in(from: Rect, to: Rect, person: Rect) -> bool {
+    let gap = distance(to, person);
+    gap < YIELD_MARGIN && gap < distance(from, person)
+}
+
+/// The approach whose green lets pedestrians cross the arm of `approach`:
+/// they walk alongside the traffic going straight across it.
+pub fn parallel_approach(approach: Approach) -> Approach {
+    match approach {
+        Approach::North | Approach::South => Approach::West,
+        Approach::West | Approach::East => Approach::North,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pedestrian {
+    pub id: u32,
+    pub crosswalk: Approach,
+    /// Walking towards the larger coordinate.
+    pub forward: bool,
+    /// Pixels from the middle line of the crosswalk, so that people spread out.
+    pub offset: f32,
+    /// Pixels walked from the curb.
+    pub walked: f32,
+    /// Pixels per tick.
+    pub speed: f32,
+    pub arrival_tick: u64,
+    /// When the pedestrian stepped off the curb.
+    pub start_tick: Option<u64>,
+}
+
+impl Pedestrian {
+    pub fn is_walking(&self) -> bool {
+        self.start_tick.is_some()
+    }
+
+    /// Pixels from one curb to the other.
+    pub fn length(&self) -> f32 {
+        let zone = crosswalk(self.crosswalk);
+        zone.w.max(zone.h) + 2.0 * CURB
+    }
+
+    pub fn has_crossed(&self) -> bool {
+        self.walked >= self.length()
+    }
+
+    pub fn position(&self) -> Vec2 {
+        let zone = crosswalk(self.crosswalk);
+        let along = if self.forward {
+            self.walked
+        } else {
+            self.length() - self.walked
+        } - CURB;
+        if zone.w > zone.h {
+            vec2(zone.x + along, zone.center().y + self.offset)
+        } else {
+            vec2(zone.center().x + self.offset, zone.y + along)
+        }
+    }
+
+    pub fn body(&self) -> Rect {
+        let position = self.position();
+        Rect::new(
+            position.x - RADIUS,
+            position.y - RADIUS,
+            2.0 * RADIUS,
+            2.0 * RADIUS,
+        )
+    }
+
+    pub fn draw(&self) {
+        let position = self.position();
+        draw_circle(position.x, position.y, RADIUS, PEDESTRIAN_COLOR);
+        draw_circle_lines(position.x, position.y, RADIUS, 1.5, DARKGRAY);
+    }
+}
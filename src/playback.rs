@@ -0,0 +1,128 @@
+use crate::camera::ViewCamera;
+use crate::constants::*;
+use crate::intersection::ControlState;
+use crate::recording::Frame;
+use crate::vehicule::{draw_vehicule, Textures};
+use macroquad::prelude::*;
+
+const BAR_MARGIN: f32 = 20.0;
+const BAR_HEIGHT: f32 = 12.0;
+
+/// Viewer for a recording: Space play/pause, Left/Right seek one second
+/// (ten with Shift), Home/End jump, click or drag the bar to scrub.
+pub async fn run(frames: Vec<Frame>, road: Texture2D, textures: Textures) {
+    let mut current: usize = 0;
+    let mut playing = true;
+    let seconds = (1.0 / TICK).round() as usize;
+    let mut view = ViewCamera::new();
+
+    loop {
+        view.handle_input();
+        clear_background(WHITE);
+        set_camera(&view.camera());
+        draw_texture(&road, 0.0, 0.0, WHITE);
+
+        if frames.is_empty() {
+            set_default_camera();
+            draw_text("Empty recording", 20.0, 40.0, 30.0, BLACK);
+        } else {
+            let last = frames.len() - 1;
+            let step = if is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift) {
+                10 * seconds
+            } else {
+                seconds
+            };
+            if is_key_pressed(KeyCode::Space) {
+                playing = !playing;
+            }
+            if is_key_pressed(KeyCode::Right) {
+                current = (current + step).min(last);
+            }
+            if is_key_pressed(KeyCode::Left) {
+                current = current.saturating_sub(step);
+            }
+            if is_key_pressed(KeyCode::Home) {
+                current = 0;
+            }
+            if is_key_pressed(KeyCode::End) {
+                current = last;
+            }
+
+            let bar_y = screen_height() - BAR_MARGIN - BAR_HEIGHT;
+            let bar_width = screen_width() - 2.0 * BAR_MARGIN;
+            let (mouse_x, mouse_y) = mouse_position();
+            if is_mouse_button_down(MouseButton::Left)
+                && !view.is_dragging()
+                && (mouse_y - bar_y).abs() < 2.0 * BAR_HEIGHT
+            {
+                let fraction = ((mouse_x - BAR_MARGIN) / bar_width).clamp(0.0, 1.0);
+                current = (fraction * last as f32).round() as usize;
+            }
+
+            let frame = &frames[current];
+            for vehicle in &frame.vehicles {
+                draw_vehicule(
+                    vehicle.route,
+                    vehicle.kind,
+                    vehicle.coordonne,
+                    vehicle.rotation,
+                    &textures,
+                );
+                draw_circle(
+                    vehicle.coordonne.x,
+                    vehicle.coordonne.y,
+                    4.0,
+                    state_color(vehicle.state),
+                );
+            }
+
+            set_default_camera();
+            draw_rectangle(BAR_MARGIN, bar_y, bar_width, BAR_HEIGHT, LIGHTGRAY);
+            let progress = if last == 0 {
+                1.0
+            } else {
+                current as f32 / last as f32
+            };
+            draw_rectangle(
+                BAR_MARGIN,
+                bar_y,
+                bar_width * progress,
+                BAR_HEIGHT,
+                DARKGRAY,
+            );
+            let status = if playing { "Playing" } else { "Paused" };
+            draw_text(
+                &format!(
+                    "{} {:.2} s / {:.2} s  vehicles: {}",
+                    status,
+                    frame.tick as f32 * TICK,
+                    frames[last].tick as f32 * TICK,
+                    frame.vehicles.len()
+                ),
+                BAR_MARGIN,
+                bar_y - 10.0,
+                24.0,
+                BLACK,
+            );
+
+            if playing && current < last {
+                current += 1;
+            }
+        }
+
+        if is_key_pressed(KeyCode::Escape) {
+            std::process::exit(0);
+        }
+
+        next_frame().await;
+    }
+}
+
+fn state_color(state: ControlState) -> Color {
+    match state {
+        ControlState::Approaching => YELLOW,
+        ControlState::Waiting => RED,
+        ControlState::Crossing => GREEN,
+        ControlState::Cleared => GRAY,
+    }
+}
@@ -0,0 +1,255 @@
+use crate::intersection::*;
+use crate::route::Route;
+use crate::vehicule::VehicleType;
+use macroquad::prelude::*;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+
+const MAGIC: &[u8; 6] = b"SRREC1";
+const VEHICLE_SIZE: usize = 23;
+
+/// One vehicle as seen on one tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VehicleFrame {
+    pub id: u32,
+    pub coordonne: Vec2,
+    pub rotation: f32,
+    pub speed: f32,
+    pub route: Route,
+    pub kind: VehicleType,
+    pub state: ControlState,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub tick: u64,
+    pub vehicles: Vec<VehicleFrame>,
+}
+
+/// Streams one frame per tick to a binary file.
+///
+/// Layout: `SRREC1`, then per frame the tick (`u64`), the vehicle count
+/// (`u32`) and for each vehicle id, x, y, rotation, speed, route, type and
+/// controller state, all little endian.
+pub struct Recorder {
+    out: BufWriter<File>,
+    /// Tick and file offset of every frame written so far.
+    frames: Vec<(u64, u64)>,
+    len: u64,
+}
+
+impl Recorder {
+    pub fn create(path: &str) -> Result<Recorder, Box<dyn Error>> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        Ok(Recorder {
+            out,
+            frames: Vec::new(),
+            len: MAGIC.len() as u64,
+        })
+    }
+
+    pub fn capture(&mut self, intersection: &Intersection) -> Result<(), Box<dyn Error>> {
+        let cars = intersection.vehicules();
+        self.frames.push((intersection.tick, self.len));
+        self.len += 12 + (cars.len() * VEHICLE_SIZE) as u64;
+        self.out.write_all(&intersection.tick.to_le_bytes())?;
+        self.out.write_all(&(cars.len() as u32).to_le_bytes())?;
+        for car in cars {
+            self.out.write_all(&car.id.to_le_bytes())?;
+            self.out.write_all(&car.coordonne.x.to_le_bytes())?;
+            self.out.write_all(&car.coordonne.y.to_le_bytes())?;
+            self.out.write_all(&car.rotation.to_le_bytes())?;
+            self.out.write_all(&car.speed().to_le_bytes())?;
+            self.out.write_all(&[
+                index_of(&Route::ALL, car.route),
+                index_of(&VehicleType::ALL, car.kind),
+                index_of(&ControlState::ALL, intersection.control_state(car)),
+            ])?;
+        }
+        Ok(())
+    }
+
+    /// Drops the frames recorded after `tick`, for a run restored to it.
+    pub fn rewind(&mut self, tick: u64) -> Result<(), Box<dyn Error>> {
+        let keep = self.frames.partition_point(|(t, _)| *t <= tick);
+        if let Some(&(_, offset)) = self.frames.get(keep) {
+            self.out.flush()?;
+            self.out.get_ref().set_len(offset)?;
+            self.out.seek(SeekFrom::Start(offset))?;
+            self.frames.truncate(keep);
+            self.len = offset;
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+pub fn load(path: &str) -> Result<Vec<Frame>, Box<dyn Error>> {
+    let bytes = fs::read(path)?;
+    if !bytes.starts_with(MAGIC) {
+        return Err(format!("{} is not a simulation recording", path).into());
+    }
+    let mut reader = Reader {
+        bytes: &bytes,
+        at: MAGIC.len(),
+    };
+    let mut frames = Vec::new();
+    while reader.at < bytes.len() {
+        let tick = u64::from_le_bytes(reader.take()?);
+        let count = u32::from_le_bytes(reader.take()?) as usize;
+        if bytes.len() - reader.at < count * VEHICLE_SIZE {
+            return Err("truncated recording".into());
+        }
+        let mut vehicles = Vec::with_capacity(count);
+        for _ in 0..count {
+            let id = u32::from_le_bytes(reader.take()?);
+            let x = f32::from_le_bytes(reader.take()?);
+            let y = f32::from_le_bytes(reader.take()?);
+            let rotation = f32::from_le_bytes(reader.take()?);
+            let speed = f32::from_le_bytes(reader.take()?);
+            let [route, kind, state] = reader.take()?;
+            vehicles.push(VehicleFrame {
+                id,
+                coordonne: vec2(x, y),
+                rotation,
+                speed,
+                route: from_index(&Route::ALL, route)?,
+                kind: from_index(&VehicleType::ALL, kind)?,
+                state: from_index(&ControlState::ALL, state)?,
+            });
+        }
+        frames.push(Frame { tick, vehicles });
+    }
+    Ok(frames)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], Box<dyn Error>> {
+        let chunk = self
+            .bytes
+            .get(self.at..self.at + N)
+            .ok_or("truncated recording")?;
+        self.at += N;
+        Ok(chunk.try_into()?)
+    }
+}
+
+fn index_of<T: PartialEq>(all: &[T], value: T) -> u8 {
+    all.iter().position(|v| *v == value).unwrap() as u8
+}
+
+fn from_index<T: Copy>(all: &[T], index: u8) -> Result<T, Box<dyn Error>> {
+    all.get(index as usize)
+        .copied()
+        .ok_or_else(|| format!("invalid value {} in recording", index).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{scenario, temp_path};
+
+    fn frame_of(intersection: &Intersection) -> Frame {
+        Frame {
+            tick: intersection.tick,
+            vehicles: intersection
+                .vehicules()
+                .into_iter()
+                .map(|car| VehicleFrame {
+                    id: car.id,
+                    coordonne: car.coordonne,
+                    rotation: car.rotation,
+                    speed: car.speed(),
+                    route: car.route,
+                    kind: car.kind,
+                    state: intersection.control_state(car),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn decodes_what_was_recorded() {
+        let scenario = scenario(3);
+        let mut intersection = scenario.build();
+        let path = temp_path("round_trip.srrec");
+        let mut recorder = Recorder::create(&path).unwrap();
+        let mut expected = Vec::new();
+        while intersection.time() < 20.0 {
+            scenario.spawn(&mut intersection);
+            intersection.step();
+            recorder.capture(&intersection).unwrap();
+            expected.push(frame_of(&intersection));
+        }
+        recorder.finish().unwrap();
+        let frames = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(expected.iter().any(|f| !f.vehicles.is_empty()));
+        assert_eq!(frames, expected);
+    }
+
+    #[test]
+    fn a_rewind_drops_the_later_frames() {
+        let scenario = scenario(3);
+        let mut intersection = scenario.build();
+        let path = temp_path("rewind.srrec");
+        let mut recorder = Recorder::create(&path).unwrap();
+        let mut expected = Vec::new();
+        let mut saved = None;
+        while intersection.time() < 20.0 {
+            scenario.spawn(&mut intersection);
+            intersection.step();
+            recorder.capture(&intersection).unwrap();
+            expected.push(frame_of(&intersection));
+            if intersection.tick == 600 {
+                saved = Some((intersection.clone(), expected.len()));
+            }
+        }
+        let (restored, kept) = saved.unwrap();
+        intersection = restored;
+        recorder.rewind(intersection.tick).unwrap();
+        expected.truncate(kept);
+        while intersection.time() < 15.0 {
+            scenario.spawn(&mut intersection);
+            intersection.step();
+            recorder.capture(&intersection).unwrap();
+            expected.push(frame_of(&intersection));
+        }
+        recorder.finish().unwrap();
+        let frames = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(frames.windows(2).all(|w| w[0].tick < w[1].tick));
+        assert_eq!(frames, expected);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let path = temp_path("not_a_recording");
+        fs::write(&path, b"time,route\n").unwrap();
+        assert!(load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_truncated_recording() {
+        let path = temp_path("truncated.srrec");
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(7u64.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes());
+        bytes.extend([0; VEHICLE_SIZE - 1]);
+        fs::write(&path, bytes).unwrap();
+        assert!(load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
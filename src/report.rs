@@ -0,0 +1,394 @@
+use crate::statistics::*;
+use crate::units::SpeedUnit;
+use macroquad::prelude::*;
+use std::num::NonZeroUsize;
+
+const PAGES: [&str; 6] = [
+    "Overview",
+    "Routes",
+    "Approaches",
+    "Distributions",
+    "Over time",
+    "Baseline",
+];
+const MARGIN: f32 = 60.0;
+
+pub fn draw_stats_text(text: &str, y_offset: f32) {
+    let dims = measure_text(text, None, 30, 1.0);
+    draw_text_ex(
+        text,
+        screen_width() / 2.0 - dims.width / 2.0,
+        y_offset,
+        TextParams {
+            font_size: 30,
+            color: BLACK,
+            ..Default::default()
+        },
+    );
+}
+
+pub fn los_color(level: LevelOfService) -> Color {
+    match level {
+        LevelOfService::A => DARKGREEN,
+        LevelOfService::B => GREEN,
+        LevelOfService::C => GOLD,
+        LevelOfService::D => ORANGE,
+        LevelOfService::E => RED,
+        LevelOfService::F => MAROON,
+    }
+}
+
+/// End-of-run statistics screen, ←/→ change page.
+pub struct Report {
+    pub page: usize,
+    pub baseline: Option<Summary>,
+    pub message: String,
+    pub unit: SpeedUnit,
+}
+
+impl Report {
+    pub fn new(baseline: Option<Summary>, unit: SpeedUnit) -> Self {
+        Self {
+            page: 0,
+            baseline,
+            message: String::new(),
+            unit,
+        }
+    }
+
+    pub fn handle_keys(&mut self) {
+        if is_key_pressed(KeyCode::Right) {
+            self.page = (self.page + 1) % PAGES.len();
+        }
+        if is_key_pressed(KeyCode::Left) {
+            self.page = (self.page + PAGES.len() - 1) % PAGES.len();
+        }
+    }
+
+    pub fn draw(&self, statistics: &Statistics) {
+        clear_background(WHITE);
+        draw_stats_text(
+            &format!("{} ({}/{})", PAGES[self.page], self.page + 1, PAGES.len()),
+            40.0,
+        );
+        match self.page {
+            0 => draw_overview(statistics, self.unit),
+            1 => draw_table(&statistics.routes, &statistics.overall()),
+            2 => draw_table(&statistics.approaches, &statistics.overall()),
+            3 => draw_distributions(statistics, self.unit),
+            4 => draw_over_time(statistics),
+            _ => self.draw_baseline(statistics),
+        }
+        draw_text(
+            &self.message,
+            MARGIN,
+            screen_height() - 50.0,
+            20.0,
+            DARKGRAY,
+        );
+        draw_text(
+            "<- / ->: page   S: save as baseline   SPACE: quit",
+            MARGIN,
+            screen_height() - 20.0,
+            22.0,
+            DARKGRAY,
+        );
+    }
+
+    fn draw_baseline(&self, statistics: &Statistics) {
+        let Some(baseline) = &self.baseline else {
+            draw_stats_text("No baseline loaded (--baseline <file>)", 120.0);
+            return;
+        };
+        let current = statistics.summary();
+        let rows = [
+            ("Duration (s)", baseline.duration, current.duration),
+            (
+                "Vehicles passed",
+                baseline.passed as f32,
+                current.passed as f32,
+            ),
+            (
+                "Throughput (veh/min)",
+                baseline.throughput,
+                current.throughput,
+            ),
+            (
+                "Mean travel time (s)",
+                baseline.mean_travel_time,
+                current.mean_travel_time,
+            ),
+            (
+                "Average delay (s)",
+                baseline.average_delay,
+                current.average_delay,
+            ),
+            ("Max time (s)", baseline.max_time, current.max_time),
+            (
+                "Average speed (m/s)",
+                baseline.average_speed,
+                current.average_speed,
+            ),
+            (
+                "Close calls",
+                baseline.close_calls as f32,
+                current.close_calls as f32,
+            ),
+            (
+                "Collisions",
+                baseline.collisions as f32,
+                current.collisions as f32,
+            ),
+        ];
+        let columns = [MARGIN, 360.0, 520.0, 680.0];
+        let mut y = 100.0;
+        for (text, x) in ["Metric", "Baseline", "This run", "Change"]
+            .iter()
+            .zip(columns)
+        {
+            draw_text(text, x, y, 24.0, DARKGRAY);
+        }
+        for (name, before, after) in rows {
+            y += 34.0;
+            let change = if before != 0.0 {
+                format!("{:+.1} %", (after - before) / before.abs() * 100.0)
+            } else {
+                format!("{:+.2}", after - before)
+            };
+            draw_text(name, columns[0], y, 24.0, BLACK);
+            draw_text(&format!("{:.2}", before), columns[1], y, 24.0, BLACK);
+            draw_text(&format!("{:.2}", after), columns[2], y, 24.0, BLACK);
+            draw_text(&change, columns[3], y, 24.0, BLACK);
+        }
+    }
+}
+
+fn draw_overview(statistics: &Statistics, unit: SpeedUnit) {
+    let summary = statistics.summary();
+    let overall = statistics.overall();
+    let mut lines = vec![
+        format!("Vehicles passed: {}", statistics.passed_intersection),
+        format!("Max speed: {}", unit.format(statistics.max_speed)),
+        format!(
+            "Average speed: {} (p50 {}, p95 {})",
+            unit.format(overall.speed.mean),
+            unit.format(overall.speed.p50),
+            unit.format(overall.speed.p95)
+        ),
+        format!("Max time: {:.2} s", statistics.max_time.as_secs_f32()),
+        format!("Min time: {:.2} s", statistics.min_time.as_secs_f32()),
+        format!("Close calls: {}", statistics.close_calls),
+        format!("Collisions: {}", statistics.collisions),
+        format!("Throughput: {:.1} veh/min", summary.throughput),
+        format!("Average delay: {:.2} s", summary.average_delay),
+        format!(
+            "Delay p50 / p95: {:.2} s / {:.2} s",
+            overall.delay.p50, overall.delay.p95
+        ),
+        format!("Level of service: {}", los_label(overall.level_of_service)),
+        format!(
+            "Stops per vehicle: {:.2}, stopped {:.2} s",
+            overall.stops.mean, overall.stopped_time.mean
+        ),
+    ];
+    if summary.emergency_vehicles > 0 {
+        lines.push(format!(
+            "Emergency: {} vehicles, delay {:.2} s, held {} others for {:.1} s",
+            summary.emergency_vehicles,
+            summary.emergency_delay,
+            summary.vehicles_held,
+            summary.held_time
+        ));
+    }
+    if summary.pedestrians > 0 {
+        lines.push(format!(
+            "Pedestrians: {}, wait {:.2} s (p95 {:.2} s), close calls {}, collisions {}",
+            summary.pedestrians,
+            summary.pedestrian_wait,
+            summary.pedestrian_wait_p95,
+            summary.pedestrian_close_calls,
+            summary.pedestrian_collisions
+        ));
+    }
+    if summary.cyclists > 0 {
+        lines.push(format!(
+            "Cyclists: {}, delay {:.2} s, right-turn close calls {}, collisions {}",
+            summary.cyclists,
+            summary.cyclist_delay,
+            summary.cyclist_close_calls,
+            summary.cyclist_collisions
+        ));
+    }
+    if let (Some(human), Some(autonomous)) = (statistics.driver(true), statistics.driver(false)) {
+        if human.vehicles > 0 {
+            lines.push(format!(
+                "Human drivers: {} vehicles, delay {:.2} s (autonomous {:.2} s)",
+                human.vehicles, human.delay.mean, autonomous.delay.mean
+            ));
+        }
+    }
+    for (index, line) in lines.iter().enumerate() {
+        draw_stats_text(line, 100.0 + index as f32 * 45.0);
+    }
+}
+
+fn draw_table(groups: &[GroupStatistics], overall: &GroupStatistics) {
+    let columns = [
+        MARGIN, 180.0, 280.0, 390.0, 500.0, 610.0, 720.0, 810.0, 920.0,
+    ];
+    let mut y = 100.0;
+    for (text, x) in [
+        "",
+        "Vehicles",
+        "Travel (s)",
+        "Delay (s)",
+        "Delay p50",
+        "Delay p95",
+        "Stops",
+        "Stopped (s)",
+        "LOS",
+    ]
+    .iter()
+    .zip(columns)
+    {
+        draw_text(text, x, y, 20.0, DARKGRAY);
+    }
+    for group in groups.iter().chain(std::iter::once(overall)) {
+        y += 30.0;
+        let cells = [
+            group.name.clone(),
+            group.vehicles.to_string(),
+            format!("{:.2}", group.travel_time.mean),
+            format!("{:.2}", group.delay.mean),
+            format!("{:.2}", group.delay.p50),
+            format!("{:.2}", group.delay.p95),
+            format!("{:.2}", group.stops.mean),
+            format!("{:.2}", group.stopped_time.mean),
+        ];
+        for (text, x) in cells.iter().zip(columns) {
+            draw_text(text, x, y, 20.0, BLACK);
+        }
+        draw_text(
+            &los_label(group.level_of_service),
+            columns[8],
+            y,
+            20.0,
+            group.level_of_service.map_or(BLACK, los_color),
+        );
+    }
+    draw_text(
+        "Delay is travel time minus the free-flow time of the route, stops and stopped time are means per vehicle.",
+        MARGIN,
+        y + 40.0,
+        16.0,
+        DARKGRAY,
+    );
+}
+
+fn draw_distributions(statistics: &Statistics, unit: SpeedUnit) {
+    let travel: Vec<f32> = statistics.trips.iter().map(|t| t.travel_time()).collect();
+    let delay: Vec<f32> = statistics.trips.iter().map(|t| t.delay).collect();
+    let speed: Vec<f32> = statistics
+        .trips
+        .iter()
+        .map(|t| unit.convert(t.average_speed))
+        .collect();
+    let height = (screen_height() - 320.0) / 3.0;
+    draw_histogram("Travel time (s)", &travel, 100.0, height);
+    draw_histogram("Delay (s)", &delay, 160.0 + height, height);
+    draw_histogram(
+        &format!("Average speed ({})", unit.symbol()),
+        &speed,
+        220.0 + 2.0 * height,
+        height,
+    );
+}
+
+fn draw_histogram(title: &str, values: &[f32], top: f32, height: f32) {
+    let width = screen_width() - 2.0 * MARGIN;
+    draw_text(title, MARGIN, top, 22.0, BLACK);
+    if values.is_empty() {
+        draw_text(
+            "No vehicle went through",
+            MARGIN,
+            top + 40.0,
+            22.0,
+            DARKGRAY,
+        );
+        return;
+    }
+    let bins = 12;
+    let (bin_width, counts) = histogram(values, NonZeroUsize::new(bins).unwrap());
+    let max = counts.iter().copied().max().unwrap_or(1).max(1) as f32;
+    let bar = width / bins as f32;
+    let bottom = top + height;
+    for (index, count) in counts.iter().enumerate() {
+        let h = *count as f32 / max * (height - 30.0);
+        let x = MARGIN + index as f32 * bar;
+        draw_rectangle(x + 2.0, bottom - h, bar - 4.0, h, SKYBLUE);
+        draw_text(&count.to_string(), x + 4.0, bottom - h - 4.0, 16.0, BLACK);
+        draw_text(
+            &format!("{:.1}", index as f32 * bin_width),
+            x,
+            bottom + 16.0,
+            16.0,
+            DARKGRAY,
+        );
+    }
+    draw_line(MARGIN, bottom, MARGIN + width, bottom, 1.0, BLACK);
+}
+
+fn draw_over_time(statistics: &Statistics) {
+    let height = (screen_height() - 260.0) / 2.0;
+    draw_chart(
+        "Throughput (veh/min)",
+        &statistics.throughput_series(),
+        100.0,
+        height,
+    );
+    draw_chart(
+        "Queued vehicles",
+        &statistics.queue_series(),
+        160.0 + height,
+        height,
+    );
+}
+
+/// Line chart of `(time, value)` points.
+fn draw_chart(title: &str, points: &[(f32, f32)], top: f32, height: f32) {
+    let width = screen_width() - 2.0 * MARGIN;
+    draw_text(title, MARGIN, top, 22.0, BLACK);
+    let bottom = top + height;
+    draw_line(MARGIN, bottom, MARGIN + width, bottom, 1.0, BLACK);
+    draw_line(MARGIN, top + 10.0, MARGIN, bottom, 1.0, BLACK);
+    if points.len() < 2 {
+        draw_text("Not enough data", MARGIN + 10.0, top + 40.0, 22.0, DARKGRAY);
+        return;
+    }
+    let max_time = points.last().map(|p| p.0).unwrap_or(1.0).max(1.0);
+    let max_value = points.iter().map(|p| p.1).fold(0.0, f32::max).max(1.0);
+    let to_screen = |(time, value): (f32, f32)| {
+        vec2(
+            MARGIN + time / max_time * width,
+            bottom - value / max_value * (height - 30.0),
+        )
+    };
+    for pair in points.windows(2) {
+        let (a, b) = (to_screen(pair[0]), to_screen(pair[1]));
+        draw_line(a.x, a.y, b.x, b.y, 2.0, DARKBLUE);
+    }
+    draw_text(
+        &format!("{:.1}", max_value),
+        MARGIN + 4.0,
+        top + 26.0,
+        16.0,
+        DARKGRAY,
+    );
+    draw_text(
+        &format!("{:.0} s", max_time),
+        MARGIN + width - 40.0,
+        bottom + 16.0,
+        16.0,
+        DARKGRAY,
+    );
+}
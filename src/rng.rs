@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_INC: u64 = 1442695040888963407;
+const MULTIPLIER: u64 = 6364136223846793005;
+
+/// PCG generator, same algorithm as `macroquad::rand` but owned by the
+/// simulation so a seed replays exactly the same run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        let mut rng = Rng { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let oldstate = self.state;
+        self.state = oldstate.wrapping_mul(MULTIPLIER).wrapping_add(DEFAULT_INC);
+        let xorshifted: u32 = (((oldstate >> 18) ^ oldstate) >> 27) as u32;
+        let rot: u32 = (oldstate >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn gen_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+
+    /// Uniform in `[low, high)`.
+    pub fn gen_range(&mut self, low: usize, high: usize) -> usize {
+        low + (self.gen_f32() * (high - low) as f32) as usize
+    }
+}
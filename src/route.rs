@@ -1,8 +1,36 @@
 use crate::constants::*;
 use crate::vehicule::Direction;
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Approach {
+    North,
+    South,
+    West,
+    East,
+}
+
+impl Approach {
+    pub const ALL: [Approach; 4] = [
+        Approach::North,
+        Approach::South,
+        Approach::West,
+        Approach::East,
+    ];
+
+    pub fn routes(&self) -> Vec<Route> {
+        match *self {
+            Approach::North => vec![Route::NS, Route::NW, Route::NE],
+            Approach::South => vec![Route::SN, Route::SE, Route::SW],
+            Approach::West => vec![Route::WE, Route::WS, Route::WN],
+            Approach::East => vec![Route::EW, Route::EN, Route::ES],
+        }
+    }
+}
 
-#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Route {
     NS,
     SN,
@@ -21,6 +49,30 @@ pub enum Route {
 }
 
 impl Route {
+    pub const ALL: [Route; 12] = [
+        Route::EW,
+        Route::WE,
+        Route::SN,
+        Route::NS,
+        Route::EN,
+        Route::WS,
+        Route::NW,
+        Route::SE,
+        Route::NE,
+        Route::SW,
+        Route::WN,
+        Route::ES,
+    ];
+
+    pub fn approach(&self) -> Approach {
+        match *self {
+            Route::NS | Route::NW | Route::NE => Approach::North,
+            Route::SN | Route::SE | Route::SW => Approach::South,
+            Route::WE | Route::WS | Route::WN => Approach::West,
+            Route::EW | Route::EN | Route::ES => Approach::East,
+        }
+    }
+
     pub fn get_coordinates(&self) -> Vec2 {
         match *self {
             Route::NS => vec2(410.0, 0.0),
@@ -40,6 +92,70 @@ impl Route {
         }
     }
 
+    /// Coordinate along the approach where the car turns, `None` when it goes straight.
+    pub fn turn_point(&self) -> Option<f32> {
+        match *self {
+            Route::NW => Some(340.0),
+            Route::SE => Some(625.0),
+            Route::WS => Some(375.0),
+            Route::EN => Some(625.0),
+
+            Route::NE => Some(515.0),
+            Route::SW => Some(485.0),
+            Route::WN => Some(515.0),
+            Route::ES => Some(485.0),
+            _ => None,
+        }
+    }
+
+    /// Lane coordinate the car snaps to once it has turned.
+    pub fn exit_lane(&self) -> Option<f32> {
+        match *self {
+            Route::NE => Some(515.0),
+            Route::SW => Some(475.0),
+            Route::WN => Some(500.0),
+            Route::ES => Some(455.0),
+            Route::NW => Some(370.0),
+            Route::SE => Some(610.0),
+            Route::WS => Some(360.0),
+            Route::EN => Some(600.0),
+            _ => None,
+        }
+    }
+
+    pub fn exit_direction(&self) -> Direction {
+        match *self {
+            Route::NE | Route::SE => Direction::Right,
+            Route::SW | Route::NW => Direction::Left,
+            Route::WS | Route::ES => Direction::Down,
+            Route::WN | Route::EN => Direction::Up,
+            _ => self.get_direction(),
+        }
+    }
+
+    /// Top-left corner positions a car goes through, from spawn to leaving the map.
+    pub fn path(&self) -> Vec<Vec2> {
+        let start = self.get_coordinates();
+        let mut points = vec![start];
+        let mut last = start;
+        if let (Some(point), Some(lane)) = (self.turn_point(), self.exit_lane()) {
+            let (corner, snapped) = match self.approach() {
+                Approach::North | Approach::South => (vec2(start.x, point), vec2(start.x, lane)),
+                Approach::West | Approach::East => (vec2(point, start.y), vec2(lane, start.y)),
+            };
+            points.push(corner);
+            points.push(snapped);
+            last = snapped;
+        }
+        points.push(match self.exit_direction() {
+            Direction::Right => vec2(1000.0, last.y),
+            Direction::Left => vec2(-CAR_WIDTH, last.y),
+            Direction::Down => vec2(last.x, 1000.0),
+            Direction::Up => vec2(last.x, -CAR_WIDTH),
+        });
+        points
+    }
+
     pub fn get_speed(&self) -> (f32, f32) {
         match *self {
             Route::NS => (0.0, VITESSE_NORMAL),
@@ -0,0 +1,430 @@
+use crate::constants::*;
+use crate::human::HumanDrivers;
+use crate::intersection::*;
+use crate::route::*;
+use crate::units::*;
+use crate::vehicule::VehicleType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+/// A complete experiment: everything needed to rerun a simulation from a file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub seed: u64,
+    /// Simulated seconds before the run ends, `None` runs until Escape.
+    #[serde(default)]
+    pub duration: Option<f32>,
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default)]
+    pub controller: ControllerConfig,
+    #[serde(default = "default_vehicle_mix")]
+    pub vehicle_mix: BTreeMap<VehicleType, f32>,
+    #[serde(default)]
+    pub human_drivers: HumanDrivers,
+    #[serde(default)]
+    pub demand: Vec<Demand>,
+    #[serde(default)]
+    pub pedestrians: Vec<PedestrianDemand>,
+    #[serde(default)]
+    pub cyclists: Vec<CyclistDemand>,
+}
+
+/// Which movements exist at the cross intersection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layout {
+    #[serde(default = "all_routes")]
+    pub routes: Vec<Route>,
+    /// Speed in km/h of the cars granted the crossing.
+    #[serde(default = "default_speed_limit")]
+    pub speed_limit: f32,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            routes: all_routes(),
+            speed_limit: default_speed_limit(),
+        }
+    }
+}
+
+impl Layout {
+    /// The speed limit in pixels per tick.
+    pub fn vitesse_max(&self) -> f32 {
+        pixels_per_tick(self.speed_limit)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ControllerKind {
+    /// First come first served queue over conflicting routes.
+    Smart,
+    /// Fixed-time signal: north and south green together, then west and
+    /// east, with an all-red clearance in between.
+    Signal,
+    /// Grants and speeds received from another process, see `external`.
+    External,
+}
+
+impl ControllerKind {
+    /// Controllers that run inside the simulation.
+    pub const ALL: [ControllerKind; 2] = [ControllerKind::Smart, ControllerKind::Signal];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ControllerKind::Smart => "smart",
+            ControllerKind::Signal => "signal",
+            ControllerKind::External => "external",
+        }
+    }
+
+    /// The next controller, to switch while debugging. `External` is only
+    /// in the cycle while a controller process is connected.
+    pub fn next(&self, connected: bool) -> ControllerKind {
+        let mut kinds = Self::ALL.to_vec();
+        if connected {
+            kinds.push(ControllerKind::External);
+        }
+        match kinds.iter().position(|k| k == self) {
+            Some(index) => kinds[(index + 1) % kinds.len()],
+            None => kinds[0],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ControllerConfig {
+    pub kind: ControllerKind,
+    #[serde(default = "default_security_distance")]
+    pub security_distance: f32,
+    /// Seconds of green per phase of the signal.
+    #[serde(default = "default_green_time")]
+    pub green_time: f32,
+    /// Seconds of all-red between two phases of the signal.
+    #[serde(default = "default_clearance_time")]
+    pub clearance_time: f32,
+}
+
+impl Default for ControllerConfig {
+    fn default() -> Self {
+        Self {
+            kind: ControllerKind::Smart,
+            security_distance: SECURITY_DISTANCE,
+            green_time: default_green_time(),
+            clearance_time: default_clearance_time(),
+        }
+    }
+}
+
+impl ControllerConfig {
+    /// Seconds of a full signal cycle, both phases and their clearances.
+    pub fn cycle(&self) -> f32 {
+        2.0 * (self.green_time + self.clearance_time)
+    }
+
+    /// Whether the signal shows green to `approach` at `time`.
+    pub fn is_green(&self, approach: Approach, time: f32) -> bool {
+        let offset = match approach {
+            Approach::North | Approach::South => 0.0,
+            Approach::West | Approach::East => self.cycle() / 2.0,
+        };
+        let t = time.rem_euclid(self.cycle()) - offset;
+        (0.0..self.green_time).contains(&t)
+    }
+}
+
+/// Poisson arrivals on one approach.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Demand {
+    pub approach: Approach,
+    /// Vehicles per minute.
+    pub rate: f32,
+    /// Restrict the generated routes, defaults to every route of the approach.
+    #[serde(default)]
+    pub routes: Option<Vec<Route>>,
+}
+
+/// Poisson arrivals of pedestrians at the crosswalk of one arm.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PedestrianDemand {
+    pub crosswalk: Approach,
+    /// Pedestrians per minute.
+    pub rate: f32,
+    /// Walking speed in m/s.
+    #[serde(default = "default_walking_speed")]
+    pub speed: f32,
+}
+
+/// Poisson arrivals of cyclists on the bike lane of one approach.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CyclistDemand {
+    pub approach: Approach,
+    /// Cyclists per minute.
+    pub rate: f32,
+    /// Cruising speed in m/s.
+    #[serde(default = "default_cycling_speed")]
+    pub speed: f32,
+}
+
+impl Scenario {
+    pub fn load(path: &str) -> Result<Scenario, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let scenario: Scenario = toml::from_str(&text)?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        if self.duration.is_some_and(|d| !positive(d)) {
+            return Err("duration must be positive".to_string());
+        }
+        if !non_negative(self.controller.security_distance) {
+            return Err("security_distance cannot be negative".to_string());
+        }
+        if !positive(self.controller.green_time) {
+            return Err("the signal needs a positive green_time".to_string());
+        }
+        if !non_negative(self.controller.clearance_time) {
+            return Err("clearance_time cannot be negative".to_string());
+        }
+        if !(self.layout.speed_limit.is_finite() && self.layout.vitesse_max() > VITESSE_NORMAL) {
+            return Err(format!(
+                "speed_limit must be above the approach speed of {:.1} km/h",
+                metres_per_second(VITESSE_NORMAL) * 3.6
+            ));
+        }
+        if !self.vehicle_mix.values().all(|w| non_negative(*w))
+            || self.vehicle_mix.values().sum::<f32>() <= 0.0
+        {
+            return Err("vehicle_mix needs at least one positive weight".to_string());
+        }
+        let humans = &self.human_drivers;
+        if !(0.0..=1.0).contains(&humans.penetration) {
+            return Err("penetration must be between 0 and 1".to_string());
+        }
+        if !non_negative(humans.critical_gap) || !non_negative(humans.reaction_time) {
+            return Err("critical_gap and reaction_time cannot be negative".to_string());
+        }
+        if !(0.0..=1.0).contains(&humans.randomness) {
+            return Err("randomness must be between 0 and 1".to_string());
+        }
+        for demand in &self.pedestrians {
+            if !non_negative(demand.rate) || !positive(demand.speed) {
+                return Err(format!(
+                    "pedestrians on {:?} need a positive rate and speed",
+                    demand.crosswalk
+                ));
+            }
+        }
+        for demand in &self.cyclists {
+            if !non_negative(demand.rate) || !positive(demand.speed) {
+                return Err(format!(
+                    "cyclists on {:?} need a positive rate and speed",
+                    demand.approach
+                ));
+            }
+        }
+        for demand in &self.demand {
+            if !non_negative(demand.rate) {
+                return Err(format!(
+                    "the demand on {:?} needs a rate of zero or more",
+                    demand.approach
+                ));
+            }
+            if let Some(routes) = &demand.routes {
+                if let Some(route) = routes.iter().find(|r| r.approach() != demand.approach) {
+                    return Err(format!(
+                        "route {:?} does not start on {:?}",
+                        route, demand.approach
+                    ));
+                }
+                if let Some(route) = routes.iter().find(|r| !self.layout.routes.contains(r)) {
+                    return Err(format!("demand route {:?} is not in the layout", route));
+                }
+            }
+            if !demand
+                .approach
+                .routes()
+                .iter()
+                .any(|r| self.layout.routes.contains(r))
+            {
+                return Err(format!(
+                    "the layout has no route for the demand on {:?}",
+                    demand.approach
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn build(&self) -> Intersection {
+        let mut intersection =
+            Intersection::with_config(self.controller.clone(), self.layout.clone(), self.seed);
+        intersection.human_drivers = self.human_drivers.clone();
+        intersection.bike_lanes = !self.cyclists.is_empty();
+        intersection
+    }
+
+    /// Draws this tick's arrivals for every demand generator.
+    pub fn spawn(&self, intersection: &mut Intersection) {
+        for demand in &self.demand {
+            let probability = demand.rate / 60.0 * TICK;
+            if intersection.rng.gen_f32() >= probability {
+                continue;
+            }
+            let routes = match &demand.routes {
+                Some(routes) => routes.clone(),
+                None => demand.approach.routes(),
+            };
+            let kind = self.pick_vehicle_type(intersection);
+            intersection.add_car(routes, kind);
+        }
+        for demand in &self.pedestrians {
+            let probability = demand.rate / 60.0 * TICK;
+            if intersection.rng.gen_f32() < probability {
+                intersection.add_pedestrian(demand.crosswalk, pixels_per_tick(demand.speed * 3.6));
+            }
+        }
+        for demand in &self.cyclists {
+            let probability = demand.rate / 60.0 * TICK;
+            if intersection.rng.gen_f32() < probability {
+                intersection.add_cyclist(demand.approach, pixels_per_tick(demand.speed * 3.6));
+            }
+        }
+    }
+
+    fn pick_vehicle_type(&self, intersection: &mut Intersection) -> VehicleType {
+        let total: f32 = self.vehicle_mix.values().sum();
+        let mut draw = intersection.rng.gen_f32() * total;
+        for (kind, weight) in &self.vehicle_mix {
+            if draw < *weight {
+                return *kind;
+            }
+            draw -= weight;
+        }
+        VehicleType::Car
+    }
+
+    /// Runs the scenario for `duration` simulated seconds without rendering.
+    pub fn run(&self, duration: f32) -> Intersection {
+        let mut intersection = self.build();
+        while intersection.time() < duration {
+            self.spawn(&mut intersection);
+            intersection.step();
+        }
+        intersection
+    }
+
+    pub fn is_finished(&self, intersection: &Intersection) -> bool {
+        match self.duration {
+            Some(duration) => intersection.time() >= duration,
+            None => false,
+        }
+    }
+}
+
+/// Finite and at least zero, NaN fails.
+fn non_negative(x: f32) -> bool {
+    x.is_finite() && x >= 0.0
+}
+
+fn positive(x: f32) -> bool {
+    x.is_finite() && x > 0.0
+}
+
+fn all_routes() -> Vec<Route> {
+    Route::ALL.to_vec()
+}
+
+/// Crossing speed of the original model, `VITESSE_RAPID`.
+fn default_speed_limit() -> f32 {
+    metres_per_second(VITESSE_RAPID) * 3.6
+}
+
+fn default_security_distance() -> f32 {
+    SECURITY_DISTANCE
+}
+
+fn default_green_time() -> f32 {
+    20.0
+}
+
+fn default_clearance_time() -> f32 {
+    4.0
+}
+
+/// A usual walking pace.
+fn default_walking_speed() -> f32 {
+    1.3
+}
+
+fn default_cycling_speed() -> f32 {
+    4.5
+}
+
+fn default_vehicle_mix() -> BTreeMap<VehicleType, f32> {
+    BTreeMap::from([(VehicleType::Car, 1.0)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scenario;
+
+    #[test]
+    fn signal_phases_alternate_with_a_clearance() {
+        // 20 s of green and 4 s of all red per phase.
+        let signal = ControllerConfig::default();
+        let green = |approach, time| signal.is_green(approach, time);
+        assert!(green(Approach::North, 0.0));
+        assert!(green(Approach::South, 19.9));
+        assert!(!green(Approach::West, 10.0));
+        assert!(!green(Approach::North, 20.0));
+        assert!(!green(Approach::East, 23.9));
+        assert!(green(Approach::East, 24.0));
+        assert!(green(Approach::West, 43.9));
+        assert!(!green(Approach::West, 44.0));
+        assert!(!green(Approach::North, 47.9));
+        assert!(green(Approach::North, 48.0));
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let text = "seed = 1\n[controller]\nkind = \"signal\"\ngreen = 30.0\n";
+        assert!(toml::from_str::<Scenario>(text).is_err());
+    }
+
+    #[test]
+    fn rejects_nan_and_names_the_bad_field() {
+        let mut bad = scenario(1);
+        bad.demand[0].rate = f32::NAN;
+        assert!(bad.validate().is_err());
+
+        let mut bad = scenario(1);
+        bad.controller.clearance_time = -1.0;
+        assert!(bad.validate().unwrap_err().contains("clearance_time"));
+
+        let mut bad = scenario(1);
+        bad.controller.security_distance = f32::NAN;
+        assert!(bad.validate().unwrap_err().contains("security_distance"));
+
+        let mut bad = scenario(1);
+        bad.layout.speed_limit = f32::NAN;
+        assert!(bad.validate().is_err());
+
+        assert!(scenario(1).validate().is_ok());
+    }
+}
@@ -0,0 +1,89 @@
+use crate::intersection::Intersection;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+
+const VERSION: u32 = 4;
+
+/// Full simulation state on disk: vehicles, tracks, queue, statistics,
+/// random generator and clock.
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    intersection: &'a Intersection,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    version: u32,
+    intersection: Intersection,
+}
+
+pub fn save(intersection: &Intersection, path: &str) -> Result<(), Box<dyn Error>> {
+    let snapshot = SnapshotRef {
+        version: VERSION,
+        intersection,
+    };
+    fs::write(path, serde_json::to_string(&snapshot)?)?;
+    Ok(())
+}
+
+pub fn load(path: &str) -> Result<Intersection, Box<dyn Error>> {
+    let snapshot: Snapshot = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if snapshot.version != VERSION {
+        return Err(format!(
+            "snapshot version {} is not supported (expected {})",
+            snapshot.version, VERSION
+        )
+        .into());
+    }
+    Ok(snapshot.intersection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{scenario, temp_path};
+
+    #[test]
+    fn a_restored_run_goes_on_identically() {
+        let scenario = scenario(11);
+        let mut intersection = scenario.build();
+        while intersection.time() < 30.0 {
+            scenario.spawn(&mut intersection);
+            intersection.step();
+        }
+        let path = temp_path("snapshot.json");
+        save(&intersection, &path).unwrap();
+        let mut restored = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(restored.tick, intersection.tick);
+
+        for run in [&mut intersection, &mut restored] {
+            while run.time() < 60.0 {
+                scenario.spawn(run);
+                run.step();
+            }
+        }
+        assert!(intersection.number_of_passed_vehicles > 0);
+        assert_eq!(
+            restored.number_of_passed_vehicles,
+            intersection.number_of_passed_vehicles
+        );
+        assert_eq!(restored.vehicules(), intersection.vehicules());
+        assert_eq!(restored.close_calls, intersection.close_calls);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let path = temp_path("old_snapshot.json");
+        let old = serde_json::json!({
+            "version": VERSION + 1,
+            "intersection": Intersection::new(),
+        });
+        fs::write(&path, old.to_string()).unwrap();
+        let err = load(&path).unwrap_err().to_string();
+        fs::remove_file(&path).unwrap();
+        assert!(err.contains("not supported"), "{}", err);
+    }
+}
@@ -0,0 +1,600 @@
+use crate::constants::*;
+use crate::intersection::Intersection;
+use crate::route::*;
+use crate::scenario::ControllerKind;
+use crate::vehicule::VehicleType;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
+/// One vehicle that went through the intersection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trip {
+    pub id: u32,
+    pub route: Route,
+    pub kind: VehicleType,
+    pub spawn_time: f32,
+    /// When the vehicle passed the end of its approach zone.
+    pub entry_time: f32,
+    pub exit_time: f32,
+    /// Seconds the route takes a lone car at its desired speed.
+    pub free_flow_time: f32,
+    /// Travel time minus free-flow time.
+    pub delay: f32,
+    /// Smallest distance in metres between its body and another vehicle's, if
+    /// it ever had a neighbour.
+    pub min_gap: Option<f32>,
+    /// Times the vehicle was held in place after moving.
+    pub stops: u32,
+    /// Seconds spent held in place.
+    pub stopped_time: f32,
+    /// Distance driven over travel time, in m/s.
+    pub average_speed: f32,
+    /// In m/s.
+    pub max_speed: f32,
+    /// Seconds held at the stop line to give way to an emergency vehicle.
+    #[serde(default)]
+    pub held_time: f32,
+    /// Driven by a human rather than by the controller.
+    #[serde(default)]
+    pub human: bool,
+}
+
+impl Trip {
+    pub fn travel_time(&self) -> f32 {
+        self.exit_time - self.spawn_time
+    }
+}
+
+/// One pedestrian who crossed an arm of the intersection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PedestrianTrip {
+    pub id: u32,
+    pub crosswalk: Approach,
+    pub arrival_time: f32,
+    /// When the pedestrian stepped off the curb.
+    pub start_time: f32,
+    pub exit_time: f32,
+}
+
+impl PedestrianTrip {
+    /// Seconds spent at the curb.
+    pub fn wait(&self) -> f32 {
+        self.start_time - self.arrival_time
+    }
+}
+
+/// One cyclist who rode a bike lane across the intersection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CyclistTrip {
+    pub id: u32,
+    pub approach: Approach,
+    pub spawn_time: f32,
+    pub exit_time: f32,
+    pub free_flow_time: f32,
+    pub delay: f32,
+    pub stopped_time: f32,
+}
+
+impl CyclistTrip {
+    pub fn travel_time(&self) -> f32 {
+        self.exit_time - self.spawn_time
+    }
+}
+
+/// What is measured about a vehicle while it is in the system.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VehicleLog {
+    pub spawn_tick: u64,
+    pub entry_tick: Option<u64>,
+    pub min_gap: Option<f32>,
+    pub stops: u32,
+    pub stopped_ticks: u64,
+    /// Ticks since the vehicle last moved.
+    #[serde(default)]
+    pub standing_ticks: u64,
+    /// Pixels driven.
+    pub distance: f32,
+    /// Largest speed in pixels per tick.
+    pub max_speed: f32,
+    #[serde(default)]
+    pub held_ticks: u64,
+}
+
+impl VehicleLog {
+    pub fn new(spawn_tick: u64) -> Self {
+        Self {
+            spawn_tick,
+            entry_tick: None,
+            min_gap: None,
+            stops: 0,
+            stopped_ticks: 0,
+            standing_ticks: 0,
+            distance: 0.0,
+            max_speed: 0.0,
+            held_ticks: 0,
+        }
+    }
+
+    /// Records the pixels the vehicle drove this tick, zero when it was held.
+    pub fn moved(&mut self, distance: f32) {
+        self.distance += distance;
+        self.max_speed = self.max_speed.max(distance);
+        if distance > 0.0 {
+            self.standing_ticks = 0;
+            return;
+        }
+        self.stopped_ticks += 1;
+        self.standing_ticks += 1;
+        if self.standing_ticks == (MIN_STOP_TIME / TICK).round() as u64 {
+            self.stops += 1;
+        }
+    }
+
+    pub fn gap(&mut self, gap: f32) {
+        self.min_gap = Some(self.min_gap.map_or(gap, |min| min.min(gap)));
+    }
+}
+
+/// State of the intersection sampled once per `SERIES_PERIOD`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SeriesPoint {
+    pub time: f32,
+    pub passed: u32,
+    pub in_system: usize,
+    pub queues: BTreeMap<Approach, usize>,
+}
+
+/// Mean and percentiles of one measure over a group of trips.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Distribution {
+    pub mean: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub max: f32,
+}
+
+impl Distribution {
+    pub fn of(values: impl Iterator<Item = f32>) -> Self {
+        let mut values: Vec<f32> = values.collect();
+        if values.is_empty() {
+            return Self::default();
+        }
+        values.sort_by(f32::total_cmp);
+        Self {
+            mean: values.iter().sum::<f32>() / values.len() as f32,
+            p50: percentile(&values, 0.50),
+            p95: percentile(&values, 0.95),
+            max: values[values.len() - 1],
+        }
+    }
+}
+
+/// Nearest-rank percentile of already sorted values.
+fn percentile(sorted: &[f32], fraction: f32) -> f32 {
+    let rank = (fraction * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// HCM level of service, from free flow (A) to breakdown (F).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LevelOfService {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+}
+
+impl LevelOfService {
+    /// Upper bounds of average control delay in seconds for A to E at an
+    /// unsignalized intersection (HCM).
+    const THRESHOLDS: [f32; 5] = [10.0, 15.0, 25.0, 35.0, 50.0];
+    /// The same at a signalized intersection.
+    const SIGNALIZED_THRESHOLDS: [f32; 5] = [10.0, 20.0, 35.0, 55.0, 80.0];
+
+    pub fn from_delay(delay: f32, signalized: bool) -> LevelOfService {
+        const LEVELS: [LevelOfService; 5] = [
+            LevelOfService::A,
+            LevelOfService::B,
+            LevelOfService::C,
+            LevelOfService::D,
+            LevelOfService::E,
+        ];
+        LEVELS
+            .iter()
+            .zip(if signalized {
+                Self::SIGNALIZED_THRESHOLDS
+            } else {
+                Self::THRESHOLDS
+            })
+            .find(|(_, limit)| delay <= *limit)
+            .map_or(LevelOfService::F, |(level, _)| *level)
+    }
+
+    /// Level of service of a group of trips, none when no vehicle went through.
+    pub fn of(trips: &[&Trip], signalized: bool) -> Option<LevelOfService> {
+        if trips.is_empty() {
+            return None;
+        }
+        let delay = trips.iter().map(|t| t.delay).sum::<f32>() / trips.len() as f32;
+        Some(LevelOfService::from_delay(delay, signalized))
+    }
+}
+
+/// Level of service of a group, `-` when no vehicle went through.
+pub fn los_label(level: Option<LevelOfService>) -> String {
+    level.map_or("-".to_string(), |l| format!("{:?}", l))
+}
+
+/// Aggregates over the trips of one route, approach or vehicle type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupStatistics {
+    pub name: String,
+    pub vehicles: usize,
+    pub travel_time: Distribution,
+    pub delay: Distribution,
+    pub stops: Distribution,
+    pub stopped_time: Distribution,
+    /// Average speed of each vehicle, in m/s.
+    pub speed: Distribution,
+    pub level_of_service: Option<LevelOfService>,
+}
+
+impl GroupStatistics {
+    fn new(name: String, trips: &[&Trip], signalized: bool) -> Self {
+        Self {
+            name,
+            vehicles: trips.len(),
+            travel_time: Distribution::of(trips.iter().map(|t| t.travel_time())),
+            delay: Distribution::of(trips.iter().map(|t| t.delay)),
+            stops: Distribution::of(trips.iter().map(|t| t.stops as f32)),
+            stopped_time: Distribution::of(trips.iter().map(|t| t.stopped_time)),
+            speed: Distribution::of(trips.iter().map(|t| t.average_speed)),
+            level_of_service: LevelOfService::of(trips, signalized),
+        }
+    }
+}
+
+/// Headline numbers of a run, saved to compare later runs against.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub duration: f32,
+    pub passed: u32,
+    pub throughput: f32,
+    pub mean_travel_time: f32,
+    pub average_delay: f32,
+    pub max_time: f32,
+    pub close_calls: u32,
+    pub collisions: u32,
+    /// Mean of the vehicles' average speeds, in m/s.
+    #[serde(default)]
+    pub average_speed: f32,
+    #[serde(default)]
+    pub level_of_service: Option<LevelOfService>,
+    #[serde(default)]
+    pub emergency_vehicles: usize,
+    /// Mean delay of the emergency vehicles, in seconds.
+    #[serde(default)]
+    pub emergency_delay: f32,
+    /// Vehicles held to give way to an emergency vehicle, and their total seconds held.
+    #[serde(default)]
+    pub vehicles_held: usize,
+    #[serde(default)]
+    pub held_time: f32,
+    #[serde(default)]
+    pub human_vehicles: usize,
+    /// Pedestrians who crossed, and the mean and 95th percentile of their wait at the curb.
+    #[serde(default)]
+    pub pedestrians: usize,
+    #[serde(default)]
+    pub pedestrian_wait: f32,
+    #[serde(default)]
+    pub pedestrian_wait_p95: f32,
+    /// Vehicles that passed too close to or hit a pedestrian on a crosswalk.
+    #[serde(default)]
+    pub pedestrian_close_calls: u32,
+    #[serde(default)]
+    pub pedestrian_collisions: u32,
+    /// Cyclists who rode through, their mean delay, and the vehicles turning
+    /// right that passed too close to or hit one of them.
+    #[serde(default)]
+    pub cyclists: usize,
+    #[serde(default)]
+    pub cyclist_delay: f32,
+    #[serde(default)]
+    pub cyclist_close_calls: u32,
+    #[serde(default)]
+    pub cyclist_collisions: u32,
+}
+
+impl Summary {
+    pub fn load(path: &str) -> Result<Summary, Box<dyn Error>> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+pub struct Statistics {
+    pub passed_intersection: u32,
+    /// Largest speed reached by a vehicle, in m/s.
+    pub max_speed: f32,
+    pub max_time: Duration,
+    pub min_time: Duration,
+    pub close_calls: u32,
+    pub collisions: u32,
+    pub duration: f32,
+    pub trips: Vec<Trip>,
+    pub series: Vec<SeriesPoint>,
+    pub routes: Vec<GroupStatistics>,
+    pub approaches: Vec<GroupStatistics>,
+    pub kinds: Vec<GroupStatistics>,
+    /// Autonomous and human-driven vehicles.
+    pub drivers: Vec<GroupStatistics>,
+    pub pedestrians: Vec<PedestrianTrip>,
+    /// Seconds pedestrians waited at the curb.
+    pub pedestrian_wait: Distribution,
+    pub pedestrian_close_calls: u32,
+    pub pedestrian_collisions: u32,
+    pub cyclists: Vec<CyclistTrip>,
+    pub cyclist_delay: Distribution,
+    pub cyclist_close_calls: u32,
+    pub cyclist_collisions: u32,
+    /// Levels of service use the signalized thresholds.
+    pub signalized: bool,
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Statistics {
+    pub fn new() -> Self {
+        Self {
+            passed_intersection: 0,
+            max_speed: 0.0,
+            max_time: Duration::from_secs(0),
+            min_time: Duration::from_secs(u64::MAX),
+            close_calls: 0,
+            collisions: 0,
+            duration: 0.0,
+            trips: Vec::new(),
+            series: Vec::new(),
+            routes: Vec::new(),
+            approaches: Vec::new(),
+            kinds: Vec::new(),
+            drivers: Vec::new(),
+            pedestrians: Vec::new(),
+            pedestrian_wait: Distribution::default(),
+            pedestrian_close_calls: 0,
+            pedestrian_collisions: 0,
+            cyclists: Vec::new(),
+            cyclist_delay: Distribution::default(),
+            cyclist_close_calls: 0,
+            cyclist_collisions: 0,
+            signalized: false,
+        }
+    }
+
+    pub fn update(&mut self, intersection: &Intersection) {
+        self.passed_intersection = intersection.number_of_passed_vehicles;
+        self.close_calls = intersection.close_calls;
+        self.collisions = intersection.collisions;
+        self.max_time = intersection.max_time;
+        self.min_time = intersection.min_time;
+        self.duration = intersection.time();
+        self.signalized = intersection.controller.kind == ControllerKind::Signal;
+        self.trips = intersection.trips.clone();
+        self.series = intersection.series.clone();
+        self.max_speed = self.trips.iter().map(|t| t.max_speed).fold(0.0, f32::max);
+
+        self.routes = Route::ALL
+            .iter()
+            .map(|route| {
+                let trips: Vec<&Trip> = self.trips.iter().filter(|t| t.route == *route).collect();
+                GroupStatistics::new(format!("{:?}", route), &trips, self.signalized)
+            })
+            .collect();
+        self.approaches = Approach::ALL
+            .iter()
+            .map(|approach| {
+                let trips: Vec<&Trip> = self
+                    .trips
+                    .iter()
+                    .filter(|t| t.route.approach() == *approach)
+                    .collect();
+                GroupStatistics::new(format!("{:?}", approach), &trips, self.signalized)
+            })
+            .collect();
+        self.kinds = VehicleType::ALL
+            .iter()
+            .map(|kind| {
+                let trips: Vec<&Trip> = self.trips.iter().filter(|t| t.kind == *kind).collect();
+                GroupStatistics::new(kind.name().to_string(), &trips, self.signalized)
+            })
+            .collect();
+        self.pedestrians = intersection.pedestrian_trips.clone();
+        self.pedestrian_wait = Distribution::of(self.pedestrians.iter().map(|p| p.wait()));
+        self.pedestrian_close_calls = intersection.pedestrian_close_calls;
+        self.pedestrian_collisions = intersection.pedestrian_collisions;
+        self.cyclists = intersection.cyclist_trips.clone();
+        self.cyclist_delay = Distribution::of(self.cyclists.iter().map(|c| c.delay));
+        self.cyclist_close_calls = intersection.cyclist_close_calls;
+        self.cyclist_collisions = intersection.cyclist_collisions;
+        self.drivers = [("autonomous", false), ("human", true)]
+            .iter()
+            .map(|(name, human)| {
+                let trips: Vec<&Trip> = self.trips.iter().filter(|t| t.human == *human).collect();
+                GroupStatistics::new(name.to_string(), &trips, self.signalized)
+            })
+            .collect();
+    }
+
+    pub fn kind(&self, kind: VehicleType) -> Option<&GroupStatistics> {
+        self.kinds.iter().find(|g| g.name == kind.name())
+    }
+
+    /// The human-driven vehicles, or the autonomous ones.
+    pub fn driver(&self, human: bool) -> Option<&GroupStatistics> {
+        self.drivers.get(human as usize)
+    }
+
+    pub fn overall(&self) -> GroupStatistics {
+        let trips: Vec<&Trip> = self.trips.iter().collect();
+        GroupStatistics::new("All".to_string(), &trips, self.signalized)
+    }
+
+    pub fn summary(&self) -> Summary {
+        let overall = self.overall();
+        Summary {
+            duration: self.duration,
+            passed: self.passed_intersection,
+            throughput: if self.duration > 0.0 {
+                self.passed_intersection as f32 / self.duration * 60.0
+            } else {
+                0.0
+            },
+            average_speed: overall.speed.mean,
+            level_of_service: overall.level_of_service,
+            mean_travel_time: overall.travel_time.mean,
+            average_delay: overall.delay.mean,
+            max_time: self.max_time.as_secs_f32(),
+            close_calls: self.close_calls,
+            collisions: self.collisions,
+            emergency_vehicles: self.kind(VehicleType::Emergency).map_or(0, |g| g.vehicles),
+            emergency_delay: self
+                .kind(VehicleType::Emergency)
+                .map_or(0.0, |g| g.delay.mean),
+            vehicles_held: self.trips.iter().filter(|t| t.held_time > 0.0).count(),
+            held_time: self.trips.iter().map(|t| t.held_time).sum(),
+            human_vehicles: self.trips.iter().filter(|t| t.human).count(),
+            pedestrians: self.pedestrians.len(),
+            pedestrian_wait: self.pedestrian_wait.mean,
+            pedestrian_wait_p95: self.pedestrian_wait.p95,
+            pedestrian_close_calls: self.pedestrian_close_calls,
+            pedestrian_collisions: self.pedestrian_collisions,
+            cyclists: self.cyclists.len(),
+            cyclist_delay: self.cyclist_delay.mean,
+            cyclist_close_calls: self.cyclist_close_calls,
+            cyclist_collisions: self.cyclist_collisions,
+        }
+    }
+
+    /// Vehicles per minute over each minute of the run, at the end of that minute.
+    pub fn throughput_series(&self) -> Vec<(f32, f32)> {
+        let mut points = Vec::new();
+        let mut start: Option<&SeriesPoint> = None;
+        for point in &self.series {
+            match start {
+                Some(from) if point.time - from.time >= 60.0 => {
+                    let minutes = (point.time - from.time) / 60.0;
+                    points.push((point.time, (point.passed - from.passed) as f32 / minutes));
+                    start = Some(point);
+                }
+                None => start = Some(point),
+                _ => {}
+            }
+        }
+        points
+    }
+
+    pub fn queue_series(&self) -> Vec<(f32, f32)> {
+        self.series
+            .iter()
+            .map(|p| (p.time, p.queues.values().sum::<usize>() as f32))
+            .collect()
+    }
+}
+
+/// Counts of `values` in `bins` equal-width bins from 0 to the largest value.
+pub fn histogram(values: &[f32], bins: NonZeroUsize) -> (f32, Vec<usize>) {
+    let bins = bins.get();
+    let max = values.iter().copied().fold(0.0, f32::max);
+    let width = if max > 0.0 { max / bins as f32 } else { 1.0 };
+    let mut counts = vec![0; bins];
+    for value in values {
+        let index = ((value.max(0.0) / width) as usize).min(bins - 1);
+        counts[index] += 1;
+    }
+    (width, counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_bins() {
+        let bins = NonZeroUsize::new(4).unwrap();
+        let (width, counts) = histogram(&[0.0, 1.0, 3.9, 4.0, 8.0], bins);
+        assert_eq!(width, 2.0);
+        assert_eq!(counts, vec![2, 1, 1, 1]);
+        let (_, counts) = histogram(&[5.0], NonZeroUsize::MIN);
+        assert_eq!(counts, vec![1]);
+    }
+
+    #[test]
+    fn unsignalized_levels_of_service() {
+        let level = |delay| LevelOfService::from_delay(delay, false);
+        assert_eq!(level(0.0), LevelOfService::A);
+        assert_eq!(level(10.0), LevelOfService::A);
+        assert_eq!(level(10.1), LevelOfService::B);
+        assert_eq!(level(15.1), LevelOfService::C);
+        assert_eq!(level(25.1), LevelOfService::D);
+        assert_eq!(level(35.1), LevelOfService::E);
+        assert_eq!(level(50.0), LevelOfService::E);
+        assert_eq!(level(50.1), LevelOfService::F);
+    }
+
+    #[test]
+    fn signalized_levels_of_service() {
+        let level = |delay| LevelOfService::from_delay(delay, true);
+        assert_eq!(level(10.0), LevelOfService::A);
+        assert_eq!(level(20.0), LevelOfService::B);
+        assert_eq!(level(35.0), LevelOfService::C);
+        assert_eq!(level(55.0), LevelOfService::D);
+        assert_eq!(level(80.0), LevelOfService::E);
+        assert_eq!(level(80.1), LevelOfService::F);
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let d = Distribution::of((1..=20).rev().map(|v| v as f32));
+        assert_eq!(d.mean, 10.5);
+        assert_eq!(d.p50, 10.0);
+        assert_eq!(d.p95, 19.0);
+        assert_eq!(d.max, 20.0);
+
+        let d = Distribution::of((1..=100).map(|v| v as f32));
+        assert_eq!(d.p50, 50.0);
+        assert_eq!(d.p95, 95.0);
+    }
+
+    #[test]
+    fn percentiles_of_few_values_are_values() {
+        let d = Distribution::of([4.0, 1.0, 3.0].into_iter());
+        assert_eq!(d.p50, 3.0);
+        assert_eq!(d.p95, 4.0);
+
+        let d = Distribution::of(std::iter::once(7.0));
+        assert_eq!((d.mean, d.p50, d.p95, d.max), (7.0, 7.0, 7.0, 7.0));
+    }
+
+    #[test]
+    fn empty_distribution_is_zero() {
+        assert_eq!(
+            Distribution::of(std::iter::empty()),
+            Distribution::default()
+        );
+    }
+}
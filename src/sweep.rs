@@ -0,0 +1,342 @@
+use crate::scenario::*;
+use crate::statistics::Statistics;
+use rayon::prelude::*;
+use std::error::Error;
+use std::fs;
+
+/// A named measure taken from the statistics of one run.
+pub type Metric = (&'static str, fn(&Statistics) -> f32);
+
+/// Measures averaged over the seeds of a combination.
+pub const METRICS: [Metric; 7] = [
+    ("throughput", |s| s.summary().throughput),
+    ("mean_delay", |s| s.overall().delay.mean),
+    ("p95_delay", |s| s.overall().delay.p95),
+    ("mean_travel_time", |s| s.overall().travel_time.mean),
+    ("stops", |s| s.overall().stops.mean),
+    ("close_calls", |s| s.close_calls as f32),
+    ("collisions", |s| s.collisions as f32),
+];
+
+/// One combination of the swept parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameters {
+    /// Factor applied to every demand rate of the scenario.
+    pub rate_scale: f32,
+    pub security_distance: f32,
+    /// In km/h.
+    pub speed_limit: f32,
+    pub controller: ControllerKind,
+    /// Share of autonomous vehicles.
+    pub penetration: f32,
+}
+
+impl Parameters {
+    /// `scenario` with these parameters and `seed`, as long as it stays valid.
+    pub fn apply(&self, scenario: &Scenario, seed: u64) -> Result<Scenario, String> {
+        let mut scenario = scenario.clone();
+        for demand in &mut scenario.demand {
+            demand.rate *= self.rate_scale;
+        }
+        scenario.controller.security_distance = self.security_distance;
+        scenario.controller.kind = self.controller;
+        scenario.layout.speed_limit = self.speed_limit;
+        scenario.human_drivers.penetration = self.penetration;
+        scenario.seed = seed;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+}
+
+/// Mean of a measure with the half width of its 95% confidence interval.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Estimate {
+    pub mean: f32,
+    pub ci95: f32,
+}
+
+impl Estimate {
+    pub fn of(values: &[f32]) -> Estimate {
+        let n = values.len();
+        let mean = values.iter().sum::<f32>() / n.max(1) as f32;
+        if n < 2 {
+            return Estimate { mean, ci95: 0.0 };
+        }
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / (n - 1) as f32;
+        Estimate {
+            mean,
+            ci95: t_critical(n - 1) * (variance / n as f32).sqrt(),
+        }
+    }
+}
+
+/// Two-sided 95% critical value of Student's t distribution. Between 30
+/// and 120 degrees of freedom it takes the value at the bracket's lower
+/// end, which errs on the wide side, and the normal value past that.
+fn t_critical(degrees_of_freedom: usize) -> f32 {
+    const TABLE: [f32; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    match degrees_of_freedom {
+        0 => f32::INFINITY,
+        df if df <= TABLE.len() => TABLE[df - 1],
+        df if df <= 40 => 2.042,
+        df if df <= 60 => 2.021,
+        df if df <= 120 => 2.000,
+        _ => 1.960,
+    }
+}
+
+/// Results of one combination over every seed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SweepRow {
+    pub parameters: Parameters,
+    pub runs: usize,
+    /// In the order of `METRICS`.
+    pub estimates: Vec<Estimate>,
+}
+
+/// Every combination of the listed values, each run with `seeds` seeds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sweep {
+    pub rate_scales: Vec<f32>,
+    pub security_distances: Vec<f32>,
+    pub speed_limits: Vec<f32>,
+    pub controllers: Vec<ControllerKind>,
+    pub penetrations: Vec<f32>,
+    pub seeds: u64,
+    pub duration: f32,
+}
+
+impl Sweep {
+    /// A sweep that only varies the seed of `scenario`.
+    pub fn new(scenario: &Scenario, seeds: u64, duration: f32) -> Sweep {
+        Sweep {
+            rate_scales: vec![1.0],
+            security_distances: vec![scenario.controller.security_distance],
+            speed_limits: vec![scenario.layout.speed_limit],
+            controllers: vec![scenario.controller.kind],
+            penetrations: vec![scenario.human_drivers.penetration],
+            seeds,
+            duration,
+        }
+    }
+
+    pub fn combinations(&self) -> Vec<Parameters> {
+        let mut combinations = Vec::new();
+        for &rate_scale in &self.rate_scales {
+            for &security_distance in &self.security_distances {
+                for &speed_limit in &self.speed_limits {
+                    for &controller in &self.controllers {
+                        for &penetration in &self.penetrations {
+                            combinations.push(Parameters {
+                                rate_scale,
+                                security_distance,
+                                speed_limit,
+                                controller,
+                                penetration,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        combinations
+    }
+
+    /// Runs every combination with seeds `scenario.seed`, `scenario.seed + 1`, ...
+    /// spread over all CPU cores. Fails on the first combination that makes
+    /// an invalid scenario.
+    pub fn run(&self, scenario: &Scenario) -> Result<Vec<SweepRow>, String> {
+        let combinations = self.combinations();
+        let runs: Vec<(usize, u64)> = (0..combinations.len())
+            .flat_map(|index| (0..self.seeds).map(move |seed| (index, seed)))
+            .collect();
+        let results: Vec<(usize, Vec<f32>)> = runs
+            .par_iter()
+            .map(|&(index, seed)| {
+                let scenario =
+                    combinations[index].apply(scenario, scenario.seed.wrapping_add(seed))?;
+                let mut statistics = Statistics::new();
+                statistics.update(&scenario.run(self.duration));
+                Ok((index, METRICS.iter().map(|(_, f)| f(&statistics)).collect()))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(combinations
+            .into_iter()
+            .enumerate()
+            .map(|(index, parameters)| {
+                let values: Vec<&Vec<f32>> = results
+                    .iter()
+                    .filter(|(i, _)| *i == index)
+                    .map(|(_, v)| v)
+                    .collect();
+                SweepRow {
+                    parameters,
+                    runs: values.len(),
+                    estimates: (0..METRICS.len())
+                        .map(|m| Estimate::of(&values.iter().map(|v| v[m]).collect::<Vec<_>>()))
+                        .collect(),
+                }
+            })
+            .collect())
+    }
+}
+
+const PARAMETER_COLUMNS: &str =
+    "rate_scale,security_distance,speed_limit,controller,penetration,runs";
+
+/// `rows` as CSV, one `<metric>_mean,<metric>_ci95` pair per measure.
+pub fn to_csv(rows: &[SweepRow]) -> String {
+    let mut text = String::from(PARAMETER_COLUMNS);
+    for (name, _) in METRICS {
+        text.push_str(&format!(",{}_mean,{}_ci95", name, name));
+    }
+    text.push('\n');
+    for row in rows {
+        let p = &row.parameters;
+        text.push_str(&format!(
+            "{},{},{},{},{},{}",
+            p.rate_scale,
+            p.security_distance,
+            p.speed_limit,
+            p.controller.name(),
+            p.penetration,
+            row.runs
+        ));
+        for estimate in &row.estimates {
+            text.push_str(&format!(",{:.4},{:.4}", estimate.mean, estimate.ci95));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+pub fn save(rows: &[SweepRow], path: &str) -> Result<(), Box<dyn Error>> {
+    fs::write(path, to_csv(rows))?;
+    Ok(())
+}
+
+/// `rows` as an aligned table of `mean ± ci95`.
+pub fn to_table(rows: &[SweepRow]) -> String {
+    let mut text = format!(
+        "{:>6} {:>8} {:>7} {:>10} {:>11}",
+        "rate", "security", "limit", "controller", "penetration"
+    );
+    for (name, _) in METRICS {
+        text.push_str(&format!(" {:>18}", name));
+    }
+    text.push('\n');
+    for row in rows {
+        let p = &row.parameters;
+        text.push_str(&format!(
+            "{:>6.2} {:>8.1} {:>7.1} {:>10} {:>11.2}",
+            p.rate_scale,
+            p.security_distance,
+            p.speed_limit,
+            p.controller.name(),
+            p.penetration
+        ));
+        for estimate in &row.estimates {
+            text.push_str(&format!(
+                " {:>18}",
+                format!("{:.2} ± {:.2}", estimate.mean, estimate.ci95)
+            ));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Values given as a list `a,b,c` or a range `start:end:step`, end included.
+pub fn parse_values(text: &str) -> Result<Vec<f32>, String> {
+    let invalid = |value: &str| format!("invalid number `{}`", value);
+    let parts: Vec<&str> = text.split(':').collect();
+    if let [start, end, step] = parts.as_slice() {
+        let start: f32 = start.trim().parse().map_err(|_| invalid(start))?;
+        let end: f32 = end.trim().parse().map_err(|_| invalid(end))?;
+        let step: f32 = step.trim().parse().map_err(|_| invalid(step))?;
+        if step <= 0.0 || end < start {
+            return Err(format!("empty range `{}`", text));
+        }
+        let count = ((end - start) / step + 1e-4).floor() as usize + 1;
+        return Ok((0..count).map(|i| start + i as f32 * step).collect());
+    }
+    text.split(',')
+        .map(|value| value.trim().parse().map_err(|_| invalid(value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scenario;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn t_table() {
+        assert_eq!(t_critical(0), f32::INFINITY);
+        assert_eq!(t_critical(1), 12.706);
+        assert_eq!(t_critical(4), 2.776);
+        assert_eq!(t_critical(30), 2.042);
+        assert_eq!(t_critical(31), 2.042);
+        assert_eq!(t_critical(40), 2.042);
+        assert_eq!(t_critical(41), 2.021);
+        assert_eq!(t_critical(60), 2.021);
+        assert_eq!(t_critical(61), 2.000);
+        assert_eq!(t_critical(120), 2.000);
+        assert_eq!(t_critical(1000), 1.960);
+    }
+
+    #[test]
+    fn confidence_interval_of_the_mean() {
+        // Sample standard deviation sqrt(2.5), standard error sqrt(0.5).
+        let estimate = Estimate::of(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(estimate.mean, 3.0);
+        assert!(
+            close(estimate.ci95, 2.776 * 0.5f32.sqrt()),
+            "{:?}",
+            estimate
+        );
+
+        let estimate = Estimate::of(&[2.0, 2.0, 2.0]);
+        assert_eq!(estimate.ci95, 0.0);
+    }
+
+    #[test]
+    fn a_single_run_has_no_interval() {
+        assert_eq!(
+            Estimate::of(&[4.0]),
+            Estimate {
+                mean: 4.0,
+                ci95: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn parses_lists_and_ranges() {
+        assert_eq!(parse_values("40, 50").unwrap(), vec![40.0, 50.0]);
+        let values = parse_values("0.5:1.5:0.25").unwrap();
+        assert_eq!(values.len(), 5);
+        assert!(close(values[4], 1.5));
+        assert!(parse_values("1:0:1").is_err());
+        assert!(parse_values("1:2:0").is_err());
+        assert!(parse_values("1,x").is_err());
+    }
+
+    #[test]
+    fn applied_parameters_are_validated() {
+        let scenario = scenario(1);
+        let mut parameters = Sweep::new(&scenario, 1, 10.0).combinations()[0].clone();
+        assert!(parameters.apply(&scenario, 1).is_ok());
+        parameters.speed_limit = 5.0;
+        assert!(parameters.apply(&scenario, 1).is_err());
+    }
+}
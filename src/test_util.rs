@@ -0,0 +1,22 @@
+//! Fixtures shared by the unit tests.
+
+use crate::scenario::Scenario;
+
+/// A file name in the temporary directory, unique to this test process.
+pub fn temp_path(name: &str) -> String {
+    std::env::temp_dir()
+        .join(format!("smart_road_{}_{}", std::process::id(), name))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Steady traffic from the north and the west, 40 vehicles per minute each.
+pub fn scenario(seed: u64) -> Scenario {
+    toml::from_str(&format!(
+        "seed = {}\n\
+         [[demand]]\napproach = \"north\"\nrate = 40.0\n\
+         [[demand]]\napproach = \"west\"\nrate = 40.0\n",
+        seed
+    ))
+    .unwrap()
+}
@@ -0,0 +1,237 @@
+use crate::constants::*;
+use crate::intersection::*;
+use crate::route::*;
+use crate::vehicule::VehicleType;
+use std::error::Error;
+use std::fs;
+
+/// First line of a trace file, optional when loading.
+const HEADER: &str = "time,route,vehicle_type";
+
+/// One `add_car` call. Recorded arrivals carry the route that was drawn,
+/// detector imports may only know the approach and keep all its routes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arrival {
+    pub time: f32,
+    pub routes: Vec<Route>,
+    pub kind: VehicleType,
+}
+
+/// Arrival sequence stored as CSV: `time,route,vehicle_type`.
+///
+/// `route` is either a route (`NS`, `WN`, ...) or an approach (`north`, ...),
+/// `vehicle_type` may be omitted and defaults to `car`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Trace {
+    pub arrivals: Vec<Arrival>,
+    next: usize,
+}
+
+impl Trace {
+    pub fn new() -> Trace {
+        Trace::default()
+    }
+
+    pub fn push(&mut self, arrival: Arrival) {
+        self.arrivals.push(arrival);
+    }
+
+    pub fn load(path: &str) -> Result<Trace, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let mut trace = Trace::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let header = number == 0 && (line == HEADER || line == "time,route");
+            if line.is_empty() || line.starts_with('#') || header {
+                continue;
+            }
+            let arrival =
+                parse_arrival(line).map_err(|err| format!("{}:{}: {}", path, number + 1, err))?;
+            trace.push(arrival);
+        }
+        trace.arrivals.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(trace)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut text = format!("{}\n", HEADER);
+        for arrival in &self.arrivals {
+            let route = match arrival.routes.as_slice() {
+                [route] => format!("{:?}", route),
+                routes => approach_name(routes[0].approach()),
+            };
+            text.push_str(&format!(
+                "{:.4},{},{}\n",
+                arrival.time,
+                route,
+                arrival.kind.name()
+            ));
+        }
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Moves the replay cursor back after the simulation was rewound to `time`.
+    pub fn seek(&mut self, time: f32) {
+        self.next = self
+            .arrivals
+            .iter()
+            .position(|a| a.time > time - TICK / 2.0)
+            .unwrap_or(self.arrivals.len());
+    }
+
+    /// Drops recorded arrivals that happen after `time`.
+    pub fn truncate(&mut self, time: f32) {
+        self.arrivals.retain(|a| a.time <= time - TICK / 2.0);
+    }
+
+    /// Replays every arrival due at the current simulation time.
+    pub fn spawn(&mut self, intersection: &mut Intersection) {
+        while let Some(arrival) = self.arrivals.get(self.next) {
+            if arrival.time > intersection.time() + TICK / 2.0 {
+                break;
+            }
+            intersection.add_car(arrival.routes.clone(), arrival.kind);
+            self.next += 1;
+        }
+    }
+}
+
+fn parse_arrival(line: &str) -> Result<Arrival, String> {
+    let fields: Vec<&str> = line.split(',').map(|f| f.trim()).collect();
+    if fields.len() < 2 {
+        return Err("expected `time,route[,vehicle_type]`".to_string());
+    }
+    let time: f32 = fields[0]
+        .parse()
+        .map_err(|_| format!("invalid time `{}`", fields[0]))?;
+    let routes = match Route::ALL.iter().find(|r| format!("{:?}", r) == fields[1]) {
+        Some(route) => vec![*route],
+        None => match Approach::ALL
+            .iter()
+            .find(|a| approach_name(**a).eq_ignore_ascii_case(fields[1]))
+        {
+            Some(approach) => approach.routes(),
+            None => return Err(format!("unknown route `{}`", fields[1])),
+        },
+    };
+    let kind = match fields.get(2) {
+        Some(name) if !name.is_empty() => VehicleType::ALL
+            .iter()
+            .find(|k| k.name() == *name)
+            .copied()
+            .ok_or_else(|| format!("unknown vehicle type `{}`", name))?,
+        _ => VehicleType::Car,
+    };
+    Ok(Arrival { time, routes, kind })
+}
+
+fn approach_name(approach: Approach) -> String {
+    format!("{:?}", approach).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::temp_path;
+
+    #[test]
+    fn parses_a_recorded_arrival() {
+        let arrival = parse_arrival("1.5000,WN,bus").unwrap();
+        assert_eq!(arrival.time, 1.5);
+        assert_eq!(arrival.routes, vec![Route::WN]);
+        assert_eq!(arrival.kind, VehicleType::Bus);
+    }
+
+    #[test]
+    fn detector_counts_keep_every_route_of_the_approach() {
+        let arrival = parse_arrival(" 12.0 , North ").unwrap();
+        assert_eq!(arrival.routes, Approach::North.routes());
+        assert_eq!(arrival.kind, VehicleType::Car);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        assert!(parse_arrival("1.0").is_err());
+        assert!(parse_arrival("soon,NS").is_err());
+        assert!(parse_arrival("1.0,XY").is_err());
+        assert!(parse_arrival("1.0,NS,tank").is_err());
+    }
+
+    #[test]
+    fn loads_a_detector_export_in_time_order() {
+        let path = temp_path("detector.csv");
+        fs::write(
+            &path,
+            "time,route,vehicle_type\n# loop 3\n4.0,south\n\n1.0,east,car\n2.5,SN,\n",
+        )
+        .unwrap();
+        let trace = Trace::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let times: Vec<f32> = trace.arrivals.iter().map(|a| a.time).collect();
+        assert_eq!(times, vec![1.0, 2.5, 4.0]);
+        assert_eq!(trace.arrivals[0].routes, Approach::East.routes());
+        assert_eq!(trace.arrivals[1].routes, vec![Route::SN]);
+        assert_eq!(trace.arrivals[2].routes, Approach::South.routes());
+    }
+
+    #[test]
+    fn only_a_header_on_the_first_line_is_skipped() {
+        let path = temp_path("header.csv");
+        fs::write(&path, "time,route\n1.0,NS\ntime,route\n").unwrap();
+        let err = Trace::load(&path).unwrap_err().to_string();
+        assert!(err.ends_with(":3: invalid time `time`"), "{}", err);
+        fs::write(&path, "times,route\n1.0,NS\n").unwrap();
+        assert!(Trace::load(&path).is_err());
+        fs::write(&path, "1.0,NS\n").unwrap();
+        assert_eq!(Trace::load(&path).unwrap().arrivals.len(), 1);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn load_errors_give_the_line() {
+        let path = temp_path("broken.csv");
+        fs::write(&path, "time,route\n1.0,NS\n2.0,nowhere\n").unwrap();
+        let err = Trace::load(&path).unwrap_err().to_string();
+        fs::remove_file(&path).unwrap();
+        assert!(err.ends_with(":3: unknown route `nowhere`"), "{}", err);
+    }
+
+    #[test]
+    fn a_rewind_replays_and_records_from_the_restored_time() {
+        let mut trace = Trace::new();
+        for time in [1.0, 2.0, 3.0] {
+            trace.push(Arrival {
+                time,
+                routes: vec![Route::NS],
+                kind: VehicleType::Car,
+            });
+        }
+        trace.next = 3;
+        // A run restored to 2.0 has yet to spawn the arrival due then.
+        trace.seek(2.0);
+        assert_eq!(trace.next, 1);
+        trace.truncate(2.0);
+        assert_eq!(trace.arrivals.len(), 1);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut trace = Trace::new();
+        trace.push(Arrival {
+            time: 0.25,
+            routes: vec![Route::EW],
+            kind: VehicleType::Car,
+        });
+        trace.push(Arrival {
+            time: 3.0,
+            routes: Approach::West.routes(),
+            kind: VehicleType::Car,
+        });
+        let path = temp_path("round_trip.csv");
+        trace.save(&path).unwrap();
+        let loaded = Trace::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, trace);
+    }
+}
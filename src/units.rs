@@ -0,0 +1,103 @@
+use crate::constants::*;
+
+/// Metres covered by `pixels` on the map.
+pub fn metres(pixels: f32) -> f32 {
+    pixels * METRES_PER_PIXEL
+}
+
+/// Converts a speed in pixels per tick, as in `Vehicule::vitesse`, to m/s.
+pub fn metres_per_second(pixels_per_tick: f32) -> f32 {
+    metres(pixels_per_tick) / TICK
+}
+
+/// Converts a speed in km/h to pixels per tick.
+pub fn pixels_per_tick(kilometres_per_hour: f32) -> f32 {
+    kilometres_per_hour / 3.6 * TICK / METRES_PER_PIXEL
+}
+
+/// Converts an acceleration in m/s² to pixels per tick, per tick.
+pub fn pixels_per_tick_squared(metres_per_second_squared: f32) -> f32 {
+    metres_per_second_squared * TICK * TICK / METRES_PER_PIXEL
+}
+
+/// Unit speeds are displayed in, chosen with `--speed-unit kmh|ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpeedUnit {
+    #[default]
+    KilometresPerHour,
+    MetresPerSecond,
+}
+
+impl SpeedUnit {
+    pub fn from_name(name: &str) -> Option<SpeedUnit> {
+        match name {
+            "kmh" | "km/h" => Some(SpeedUnit::KilometresPerHour),
+            "ms" | "m/s" => Some(SpeedUnit::MetresPerSecond),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            SpeedUnit::KilometresPerHour => "km/h",
+            SpeedUnit::MetresPerSecond => "m/s",
+        }
+    }
+
+    /// Converts `speed` from m/s to this unit.
+    pub fn convert(&self, speed: f32) -> f32 {
+        match self {
+            SpeedUnit::KilometresPerHour => speed * 3.6,
+            SpeedUnit::MetresPerSecond => speed,
+        }
+    }
+
+    /// `speed` in m/s, converted and followed by the unit symbol.
+    pub fn format(&self, speed: f32) -> String {
+        format!("{:.1} {}", self.convert(speed), self.symbol())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4 * b.abs().max(1.0)
+    }
+
+    #[test]
+    fn known_speeds() {
+        // One pixel per tick is 0.09 m sixty times a second.
+        assert!(close(metres_per_second(1.0), 5.4));
+        assert!(close(pixels_per_tick(19.44), 1.0));
+        assert!(close(metres_per_second(VITESSE_NORMAL) * 3.6, 29.16));
+        assert!(close(pixels_per_tick_squared(1.0), 1.0 / 3600.0 / 0.09));
+        assert_eq!(metres(100.0), 9.0);
+    }
+
+    #[test]
+    fn kmh_and_pixels_per_tick_round_trip() {
+        for kmh in [0.0, 5.0, 29.16, 50.0, 130.0] {
+            assert!(close(metres_per_second(pixels_per_tick(kmh)) * 3.6, kmh));
+        }
+        for pixels in [0.7, 1.5, 2.5] {
+            assert!(close(
+                pixels_per_tick(metres_per_second(pixels) * 3.6),
+                pixels
+            ));
+        }
+    }
+
+    #[test]
+    fn display_units() {
+        assert_eq!(
+            SpeedUnit::from_name("km/h"),
+            Some(SpeedUnit::KilometresPerHour)
+        );
+        assert_eq!(SpeedUnit::from_name("ms"), Some(SpeedUnit::MetresPerSecond));
+        assert_eq!(SpeedUnit::from_name("mph"), None);
+        assert_eq!(SpeedUnit::KilometresPerHour.format(10.0), "36.0 km/h");
+        assert_eq!(SpeedUnit::MetresPerSecond.format(10.0), "10.0 m/s");
+    }
+}
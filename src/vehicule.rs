@@ -1,8 +1,11 @@
 use crate::constants::*;
+use crate::human::HumanDriver;
 use crate::route::*;
+use crate::units::pixels_per_tick_squared;
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum Direction {
     Left,
     Right,
@@ -10,10 +13,77 @@ pub enum Direction {
     Up,
 }
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VehicleType {
+    Car,
+    /// Granted the crossing ahead of everyone else.
+    Emergency,
+    Van,
+    Bus,
+    Truck,
+    Motorcycle,
+}
+
+impl VehicleType {
+    pub const ALL: [VehicleType; 6] = [
+        VehicleType::Car,
+        VehicleType::Emergency,
+        VehicleType::Van,
+        VehicleType::Bus,
+        VehicleType::Truck,
+        VehicleType::Motorcycle,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            VehicleType::Car => "car",
+            VehicleType::Emergency => "emergency",
+            VehicleType::Van => "van",
+            VehicleType::Bus => "bus",
+            VehicleType::Truck => "truck",
+            VehicleType::Motorcycle => "motorcycle",
+        }
+    }
+
+    pub fn spec(&self) -> VehicleSpec {
+        let (length, width, acceleration, deceleration, turning_radius) = match *self {
+            VehicleType::Car => (CAR_WIDTH, CAR_HEIGHT, f32::INFINITY, f32::INFINITY, 5.5),
+            VehicleType::Emergency => (CAR_WIDTH, CAR_HEIGHT, f32::INFINITY, f32::INFINITY, 5.5),
+            VehicleType::Van => (60.0, 25.0, 2.5, 4.0, 6.5),
+            VehicleType::Bus => (120.0, 27.0, 1.2, 3.0, 12.0),
+            VehicleType::Truck => (105.0, 27.0, 1.0, 3.0, 11.0),
+            VehicleType::Motorcycle => (25.0, 11.0, f32::INFINITY, f32::INFINITY, 3.0),
+        };
+        VehicleSpec {
+            length,
+            width,
+            acceleration,
+            deceleration,
+            turning_radius,
+        }
+    }
+}
+
+/// Size and dynamics of a vehicle type.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VehicleSpec {
+    /// Pixels, along the direction of travel.
+    pub length: f32,
+    pub width: f32,
+    /// m/s², infinite for the types that change speed at once as cars
+    /// always did.
+    pub acceleration: f32,
+    pub deceleration: f32,
+    /// Metres. Vehicles with a wider turning circle than a car take turns
+    /// proportionally slower.
+    pub turning_radius: f32,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Vehicule {
     pub id: u32,
-    pub color: Texture2D,
+    pub kind: VehicleType,
     pub coordonne: Vec2,
     pub vitesse: (f32, f32),
     pub rotation: f32,
@@ -21,18 +91,27 @@ pub struct Vehicule {
     pub direction: Direction,
     pub route: Route,
     pub turned: bool,
+    /// Speed in pixels per tick once granted the crossing.
+    #[serde(default = "default_vitesse_max")]
+    pub vitesse_max: f32,
+    /// Speed in pixels per tick actually driven: `vitesse` is the speed the
+    /// vehicle wants, reached within its acceleration limits.
+    #[serde(default)]
+    pub allure: f32,
+    /// Set when a human drives the vehicle instead of the controller.
+    #[serde(default)]
+    pub human: Option<HumanDriver>,
 }
 
 impl Vehicule {
     pub fn new(
         coordonne: Vec2,
         rectangle: (f32, f32),
-        color: Texture2D,
+        kind: VehicleType,
         vitesse: (f32, f32),
         id: u32,
         direction: Direction,
         route: Route,
-        turned: bool,
     ) -> Vehicule {
         let rotation = match direction {
             Direction::Up => -90.0,
@@ -41,7 +120,7 @@ impl Vehicule {
             Direction::Right => 0.0,
         };
         Vehicule {
-            color,
+            kind,
             rectangle,
             coordonne,
             vitesse,
@@ -49,82 +128,219 @@ impl Vehicule {
             id,
             direction,
             route,
-            turned,
+            turned: false,
+            vitesse_max: VITESSE_RAPID,
+            allure: (vitesse.0.powi(2) + vitesse.1.powi(2)).sqrt(),
+            human: None,
         }
     }
 
+    /// A vehicle at the start of `route`, heading into the intersection.
+    pub fn spawn(route: Route, kind: VehicleType, id: u32) -> Vehicule {
+        let direction = route.get_direction();
+        let spec = kind.spec();
+        let rectangle = match direction {
+            Direction::Up | Direction::Down => (spec.width, spec.length),
+            Direction::Left | Direction::Right => (spec.length, spec.width),
+        };
+        Vehicule::new(
+            route.get_coordinates(),
+            rectangle,
+            kind,
+            route.get_speed(),
+            id,
+            direction,
+            route,
+        )
+    }
+
     pub fn move_car(&mut self) {
+        self.move_car_within(f32::INFINITY);
+    }
+
+    /// Like `move_car`, driving no more than `limit` pixels this tick.
+    pub fn move_car_within(&mut self, limit: f32) {
         if self.on_turn_point() && !self.turned {
             self.turn();
         }
-        self.coordonne = vec2(
-            self.coordonne.x + self.vitesse.0,
-            self.coordonne.y + self.vitesse.1,
+        let spec = self.kind.spec();
+        let mut wanted = self.vitesse();
+        if self.route.turn_point().is_some() && self.on_cross_road() {
+            let turn = (VehicleType::Car.spec().turning_radius / spec.turning_radius).min(1.0);
+            wanted = wanted.min(self.vitesse_max * turn);
+        }
+        self.allure = if wanted > self.allure {
+            (self.allure + pixels_per_tick_squared(spec.acceleration)).min(wanted)
+        } else {
+            (self.allure - pixels_per_tick_squared(spec.deceleration)).max(wanted)
+        }
+        .min(limit);
+        let heading = vec2(self.vitesse.0, self.vitesse.1).normalize_or_zero();
+        self.coordonne += heading * self.allure;
+    }
+
+    pub fn draw(&self, textures: &Textures) {
+        draw_vehicule(
+            self.route,
+            self.kind,
+            self.coordonne,
+            self.rotation,
+            textures,
         );
     }
 
-    pub fn draw(&self, car1: Texture2D, car2: Texture2D, car3: Texture2D) {
-        let draw_params = DrawTextureParams {
-            dest_size: Some(Vec2::new(CAR_WIDTH, CAR_HEIGHT)),
-            rotation: self.rotation.to_radians(),
-            ..Default::default()
-        };
+    /// Remaining top-left corner positions until the car leaves the map.
+    pub fn planned_path(&self) -> Vec<Vec2> {
+        let path = self.route.path();
+        let mut planned = vec![self.coordonne];
+        if self.turned {
+            planned.extend(path.last());
+        } else {
+            planned.extend(&path[1..]);
+        }
+        planned
+    }
+
+    /// Whether `point` falls on the car as it is drawn.
+    pub fn contains(&self, point: Vec2) -> bool {
+        let centre = self.coordonne + vec2(CAR_WIDTH / 2.0, CAR_HEIGHT / 2.0);
+        let half = vec2(self.rectangle.0, self.rectangle.1) / 2.0;
+        (point.x - centre.x).abs() <= half.x && (point.y - centre.y).abs() <= half.y
+    }
+
+    /// Whether the bodies of two cars, each grown by `margin`, overlap.
+    pub fn overlaps(&self, other: &Vehicule, margin: f32) -> bool {
+        let offset = vec2(CAR_WIDTH / 2.0, CAR_HEIGHT / 2.0);
+        let distance = (self.coordonne + offset) - (other.coordonne + offset);
+        distance.x.abs() < (self.rectangle.0 + other.rectangle.0) / 2.0 + margin
+            && distance.y.abs() < (self.rectangle.1 + other.rectangle.1) / 2.0 + margin
+    }
 
-        let car = match self.route {
-            Route::SE | Route::EN | Route::NW | Route::WS => &car1,
-            Route::NS | Route::SN | Route::WE | Route::EW => &car2,
-            _ => &car3,
+    pub fn body(&self) -> Rect {
+        let centre = self.coordonne + vec2(CAR_WIDTH, CAR_HEIGHT) / 2.0;
+        let (w, h) = self.rectangle;
+        Rect::new(centre.x - w / 2.0, centre.y - h / 2.0, w, h)
+    }
+
+    /// Whether the vehicle has not yet driven clear of `area` on its way out.
+    pub fn before(&self, area: Rect) -> bool {
+        let centre = self.coordonne + vec2(CAR_WIDTH, CAR_HEIGHT) / 2.0;
+        let half = vec2(self.rectangle.0, self.rectangle.1) / 2.0;
+        match self.route.exit_direction() {
+            Direction::Up => centre.y + half.y >= area.y,
+            Direction::Down => centre.y - half.y <= area.bottom(),
+            Direction::Left => centre.x + half.x >= area.x,
+            Direction::Right => centre.x - half.x <= area.right(),
+        }
+    }
+
+    /// Pixels the coming turn moves the vehicle back along its lane, which the
+    /// one following it has to leave free.
+    pub fn turn_setback(&self) -> f32 {
+        if self.turned {
+            return 0.0;
+        }
+        let (Some(point), Some(lane)) = (self.route.turn_point(), self.route.exit_lane()) else {
+            return 0.0;
         };
+        match self.direction {
+            Direction::Right | Direction::Down => (point - lane).max(0.0),
+            Direction::Left | Direction::Up => (lane - point).max(0.0),
+        }
+    }
+
+    /// Distance between the bodies of two cars, zero when they overlap.
+    pub fn gap(&self, other: &Vehicule) -> f32 {
+        let distance = (self.coordonne - other.coordonne).abs();
+        let x = distance.x - (self.rectangle.0 + other.rectangle.0) / 2.0;
+        let y = distance.y - (self.rectangle.1 + other.rectangle.1) / 2.0;
+        vec2(x.max(0.0), y.max(0.0)).length()
+    }
 
-        draw_texture_ex(car, self.coordonne.x, self.coordonne.y, WHITE, draw_params);
+    /// Speed driven, in pixels per tick.
+    pub fn speed(&self) -> f32 {
+        self.allure
+    }
+
+    /// Speed wanted, in pixels per tick.
+    pub fn vitesse(&self) -> f32 {
+        (self.vitesse.0.powi(2) + self.vitesse.1.powi(2)).sqrt()
+    }
+
+    pub fn zone_limits(&self) -> ZoneLimits {
+        zone_limits(self.direction, self.kind.spec().length)
     }
 
     pub fn avant_intersect(&self) -> bool {
+        let avant = self.zone_limits().avant;
         match self.direction {
-            Direction::Right => self.coordonne.x < AVANT_INTERSECTION.x,
-            Direction::Left => self.coordonne.x > AVANT_INTERSECTION.y,
-            Direction::Down => self.coordonne.y < AVANT_INTERSECTION.x,
-            Direction::Up => self.coordonne.y > AVANT_INTERSECTION.y,
+            Direction::Right => self.coordonne.x < avant,
+            Direction::Left => self.coordonne.x > avant,
+            Direction::Down => self.coordonne.y < avant,
+            Direction::Up => self.coordonne.y > avant,
         }
     }
 
     pub fn in_stop_zone(&self) -> bool {
-        return match self.direction {
-            Direction::Right => self.coordonne.x > APRES_INTERSECTION.x - CAR_WIDTH,
-            Direction::Left => self.coordonne.x < APRES_INTERSECTION.y,
-            Direction::Down => self.coordonne.y > APRES_INTERSECTION.x - CAR_WIDTH,
-            Direction::Up => self.coordonne.y < APRES_INTERSECTION.y,
-        };
+        let stop = self.zone_limits().stop;
+        match self.direction {
+            Direction::Right => self.coordonne.x > stop,
+            Direction::Left => self.coordonne.x < stop,
+            Direction::Down => self.coordonne.y > stop,
+            Direction::Up => self.coordonne.y < stop,
+        }
+    }
+
+    /// Pixels left to the stop line, negative past it.
+    pub fn distance_to_stop(&self) -> f32 {
+        let stop = self.zone_limits().stop;
+        match self.direction {
+            Direction::Right => stop - self.coordonne.x,
+            Direction::Left => self.coordonne.x - stop,
+            Direction::Down => stop - self.coordonne.y,
+            Direction::Up => self.coordonne.y - stop,
+        }
     }
 
     pub fn after_intersect(&self) -> bool {
+        let apres = self.zone_limits().apres;
         match self.direction {
-            Direction::Right => self.coordonne.x > APRES_INTERSECTION.y,
-            Direction::Left => self.coordonne.x < APRES_INTERSECTION.x,
-            Direction::Down => self.coordonne.y > APRES_INTERSECTION.y,
-            Direction::Up => self.coordonne.y < APRES_INTERSECTION.x,
+            Direction::Right => self.coordonne.x > apres,
+            Direction::Left => self.coordonne.x < apres,
+            Direction::Down => self.coordonne.y > apres,
+            Direction::Up => self.coordonne.y < apres,
         }
     }
 
     pub fn on_cross_road(&self) -> bool {
-        return !self.avant_intersect() && !self.after_intersect();
+        !self.avant_intersect() && !self.after_intersect()
     }
 
     pub fn speed_up(&mut self) {
         self.vitesse = match self.direction {
-            Direction::Down => (0.0, VITESSE_RAPID),
-            Direction::Up => (0.0, -VITESSE_RAPID),
-            Direction::Right => (VITESSE_RAPID, 0.0),
-            Direction::Left => (-VITESSE_RAPID, 0.0),
+            Direction::Down => (0.0, self.vitesse_max),
+            Direction::Up => (0.0, -self.vitesse_max),
+            Direction::Right => (self.vitesse_max, 0.0),
+            Direction::Left => (-self.vitesse_max, 0.0),
         }
     }
 
     pub fn is_vitesse_max(&self) -> bool {
-        return self.vitesse.0.abs() == VITESSE_RAPID || self.vitesse.1.abs() == VITESSE_RAPID;
+        self.vitesse.0.abs() == self.vitesse_max || self.vitesse.1.abs() == self.vitesse_max
     }
 
     pub fn is_vitesse_min(&self) -> bool {
-        return self.vitesse.0.abs() == VITESSE_MIN || self.vitesse.1.abs() == VITESSE_MIN;
+        self.vitesse.0.abs() == VITESSE_MIN || self.vitesse.1.abs() == VITESSE_MIN
+    }
+
+    /// Drives straight ahead at `vitesse` pixels per tick.
+    pub fn set_speed(&mut self, vitesse: f32) {
+        self.vitesse = match self.direction {
+            Direction::Down => (0.0, vitesse),
+            Direction::Up => (0.0, -vitesse),
+            Direction::Right => (vitesse, 0.0),
+            Direction::Left => (-vitesse, 0.0),
+        }
     }
 
     pub fn slow_down(&mut self) {
@@ -137,18 +353,21 @@ impl Vehicule {
     }
 
     fn on_turn_point(&self) -> bool {
-        return match self.route {
-            Route::NW => self.coordonne.y >= 340.0,
-            Route::SE => self.coordonne.y <= 625.0,
-            Route::WS => self.coordonne.x > 375.0,
-            Route::EN => self.coordonne.x < 625.0,
-
-            Route::NE => self.coordonne.y > 515.0,
-            Route::SW => self.coordonne.y < 485.0,
-            Route::WN => self.coordonne.x > 515.0,
-            Route::ES => self.coordonne.x < 485.0,
-            _ => false,
+        let Some(point) = self.route.turn_point() else {
+            return false;
         };
+        match self.route {
+            Route::NW => self.coordonne.y >= point,
+            Route::SE => self.coordonne.y <= point,
+            Route::WS => self.coordonne.x > point,
+            Route::EN => self.coordonne.x < point,
+
+            Route::NE => self.coordonne.y > point,
+            Route::SW => self.coordonne.y < point,
+            Route::WN => self.coordonne.x > point,
+            Route::ES => self.coordonne.x < point,
+            _ => false,
+        }
     }
 
     fn turn(&mut self) {
@@ -157,47 +376,50 @@ impl Vehicule {
         self.rectangle.0 = r.1;
         self.rectangle.1 = r.0;
         self.turned = true;
+        let Some(lane) = self.route.exit_lane() else {
+            return;
+        };
 
         match self.route {
             Route::NE => {
                 self.vitesse.0 = vitesse.1;
                 self.vitesse.1 = vitesse.0;
                 self.direction = Direction::Right;
-                self.coordonne.y = 515.0;
+                self.coordonne.y = lane;
                 self.rotation = 0.0;
             }
             Route::SW => {
                 self.vitesse.0 = vitesse.1;
                 self.vitesse.1 = vitesse.0;
                 self.direction = Direction::Left;
-                self.coordonne.y = 475.0;
+                self.coordonne.y = lane;
                 self.rotation = 180.0;
             }
             Route::WN => {
                 self.vitesse.0 = -vitesse.1;
                 self.vitesse.1 = -vitesse.0;
                 self.direction = Direction::Up;
-                self.coordonne.x = 500.0;
+                self.coordonne.x = lane;
                 self.rotation = 270.0;
             }
             Route::ES => {
                 self.vitesse.0 = -vitesse.1;
                 self.vitesse.1 = -vitesse.0;
                 self.direction = Direction::Down;
-                self.coordonne.x = 455.0;
+                self.coordonne.x = lane;
                 self.rotation = 90.0;
             }
             Route::NW => {
                 self.vitesse.0 = -vitesse.1;
                 self.vitesse.1 = -vitesse.0;
                 self.direction = Direction::Left;
-                self.coordonne.y = 370.0;
+                self.coordonne.y = lane;
                 self.rotation = 180.0;
             }
             Route::SE => {
                 self.vitesse.0 = -vitesse.1;
                 self.vitesse.1 = -vitesse.0;
-                self.coordonne.y = 610.0;
+                self.coordonne.y = lane;
                 self.direction = Direction::Right;
                 self.rotation = 0.0;
             }
@@ -205,26 +427,128 @@ impl Vehicule {
                 self.direction = Direction::Down;
                 self.vitesse.0 = vitesse.1;
                 self.vitesse.1 = vitesse.0;
-                self.coordonne.x = 360.0;
+                self.coordonne.x = lane;
                 self.rotation = 90.0;
             }
             Route::EN => {
                 self.direction = Direction::Up;
                 self.vitesse.0 = vitesse.1;
                 self.vitesse.1 = vitesse.0;
-                self.coordonne.x = 600.0;
+                self.coordonne.x = lane;
                 self.rotation = 270.0;
             }
-            _ => return,
+            _ => {}
+        }
+        // A long body pivots around its rear half: pushed forward so it does
+        // not swing back across the lanes it just left.
+        let extra = ((self.kind.spec().length - CAR_WIDTH) / 2.0).max(0.0);
+        match self.direction {
+            Direction::Right => self.coordonne.x += extra,
+            Direction::Left => self.coordonne.x -= extra,
+            Direction::Down => self.coordonne.y += extra,
+            Direction::Up => self.coordonne.y -= extra,
         }
     }
 
     pub fn is_out_of_road(&self) -> bool {
-        return match self.direction {
-            Direction::Right => self.coordonne.x > 1000.0,
-            Direction::Left => self.coordonne.x < 0.0 - CAR_WIDTH,
-            Direction::Down => self.coordonne.y > 1000.0,
-            Direction::Up => self.coordonne.y < 0.0 - CAR_WIDTH,
-        };
+        let extra = (self.kind.spec().length - CAR_WIDTH) / 2.0;
+        match self.direction {
+            Direction::Right => self.coordonne.x > 1000.0 + extra,
+            Direction::Left => self.coordonne.x < 0.0 - CAR_WIDTH - extra,
+            Direction::Down => self.coordonne.y > 1000.0 + extra,
+            Direction::Up => self.coordonne.y < 0.0 - CAR_WIDTH - extra,
+        }
     }
 }
+
+/// Coordinates along the travel axis where a vehicle leaves its approach,
+/// enters the stop zone and clears the intersection (top-left corner of a
+/// car centred on the vehicle). Longer vehicles reach the first two earlier
+/// and clear the intersection later, by half their extra length.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZoneLimits {
+    pub avant: f32,
+    pub stop: f32,
+    pub apres: f32,
+}
+
+pub fn zone_limits(direction: Direction, length: f32) -> ZoneLimits {
+    let extra = (length - CAR_WIDTH) / 2.0;
+    match direction {
+        Direction::Right | Direction::Down => ZoneLimits {
+            avant: AVANT_INTERSECTION.x - extra,
+            stop: APRES_INTERSECTION.x - CAR_WIDTH - extra,
+            apres: APRES_INTERSECTION.y + extra,
+        },
+        Direction::Left | Direction::Up => ZoneLimits {
+            avant: AVANT_INTERSECTION.y + extra,
+            stop: APRES_INTERSECTION.y + extra,
+            apres: APRES_INTERSECTION.x - extra,
+        },
+    }
+}
+
+/// Vehicle textures. Cars pick one of three by route, emergency vehicles
+/// are tinted cars.
+#[derive(Clone)]
+pub struct Textures {
+    pub cars: [Texture2D; 3],
+    pub van: Texture2D,
+    pub bus: Texture2D,
+    pub truck: Texture2D,
+    pub motorcycle: Texture2D,
+}
+
+impl Textures {
+    pub async fn load() -> Textures {
+        Textures {
+            cars: [
+                load_texture("./assets/car_blue.png").await.unwrap(),
+                load_texture("./assets/car_green.png").await.unwrap(),
+                load_texture("./assets/car_white.png").await.unwrap(),
+            ],
+            van: load_texture("./assets/van.png").await.unwrap(),
+            bus: load_texture("./assets/bus.png").await.unwrap(),
+            truck: load_texture("./assets/truck.png").await.unwrap(),
+            motorcycle: load_texture("./assets/motorcycle.png").await.unwrap(),
+        }
+    }
+}
+
+/// Draws a vehicle from its route, type, top-left corner and rotation,
+/// shared by the live simulation and the recording playback.
+pub fn draw_vehicule(
+    route: Route,
+    kind: VehicleType,
+    coordonne: Vec2,
+    rotation: f32,
+    textures: &Textures,
+) {
+    let spec = kind.spec();
+    let draw_params = DrawTextureParams {
+        dest_size: Some(Vec2::new(spec.length, spec.width)),
+        rotation: rotation.to_radians(),
+        ..Default::default()
+    };
+
+    let [car1, car2, car3] = &textures.cars;
+    let (texture, color) = match (kind, route) {
+        (VehicleType::Emergency, _) => (car3, EMERGENCY_COLOR),
+        (VehicleType::Van, _) => (&textures.van, WHITE),
+        (VehicleType::Bus, _) => (&textures.bus, WHITE),
+        (VehicleType::Truck, _) => (&textures.truck, WHITE),
+        (VehicleType::Motorcycle, _) => (&textures.motorcycle, WHITE),
+        (_, Route::SE | Route::EN | Route::NW | Route::WS) => (car1, WHITE),
+        (_, Route::NS | Route::SN | Route::WE | Route::EW) => (car2, WHITE),
+        _ => (car3, WHITE),
+    };
+
+    // Every vehicle is centred where a car would be.
+    let centre = coordonne + vec2(CAR_WIDTH, CAR_HEIGHT) / 2.0;
+    let corner = centre - vec2(spec.length, spec.width) / 2.0;
+    draw_texture_ex(texture, corner.x, corner.y, color, draw_params);
+}
+
+fn default_vitesse_max() -> f32 {
+    VITESSE_RAPID
+}